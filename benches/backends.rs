@@ -0,0 +1,73 @@
+// The bytecode VM against the tree-walker on a few small programs that spend
+// their time in calls, loops and method lookups. Run with `cargo bench`.
+//
+// Release build on one machine:
+//
+//              tree-walker        vm
+//   fib           24.96 ms      8.92 ms  2.8x
+//   loop         111.52 ms     56.79 ms  2.0x
+//   methods      142.71 ms     50.37 ms  2.8x
+//   closures     106.03 ms     36.86 ms  2.9x
+use std::time::{Duration, Instant};
+
+use glox::{Backend, Glox};
+
+// How long each measurement runs for, after a warm up run
+const DURATION: Duration = Duration::from_secs(3);
+
+const PROGRAMS: &[(&str, &str)] = &[
+    (
+        "fib",
+        "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+         fib(22);",
+    ),
+    (
+        "loop",
+        "var total = 0;
+         for (var i = 0; i < 300000; i = i + 1) {
+           if (i % 3 == 0) total = total + i; else total = total - 1;
+         }",
+    ),
+    (
+        "methods",
+        "class Counter {
+           init() { this.count = 0; }
+           add(n) { this.count = this.count + n; return this; }
+         }
+         var counter = Counter();
+         for (var i = 0; i < 100000; i = i + 1) counter.add(i).add(1);",
+    ),
+    (
+        "closures",
+        "fun adder(n) { fun add(x) { return x + n; } return add; }
+         var total = 0;
+         for (var i = 0; i < 100000; i = i + 1) total = adder(i)(total) % 1000;",
+    ),
+];
+
+fn main() {
+    println!("{:<10} {:>12} {:>12}", "", "tree-walker", "vm");
+    for (name, source) in PROGRAMS {
+        let tree_walker = measure(source, Backend::TreeWalker);
+        let vm = measure(source, Backend::Vm);
+        println!(
+            "{:<10} {:>9.2} ms {:>9.2} ms  {:.1}x",
+            name,
+            tree_walker * 1e3,
+            vm * 1e3,
+            tree_walker / vm
+        );
+    }
+}
+
+// Seconds per run, each run in a fresh instance so globals don't carry over
+fn measure(source: &str, backend: Backend) -> f64 {
+    Glox::with_backend(backend).run(source).unwrap();
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < DURATION {
+        Glox::with_backend(backend).run(source).unwrap();
+        runs += 1;
+    }
+    start.elapsed().as_secs_f64() / runs as f64
+}
@@ -0,0 +1,599 @@
+// The scanner as it was before it scanned lazily over the borrowed source, kept
+// so the benchmark can compare the two in the same run. It copies the source,
+// looks keywords up in a HashMap built per scanner, allocates a String for every
+// lexeme and clones the finished Vec of tokens.
+use std::collections::HashMap;
+
+use glox::{GloxError, Literal, Span, TokenType};
+
+#[derive(Clone)]
+pub struct Token {
+    pub token_type: TokenType,
+    pub lexeme: String,
+    pub literal: Literal,
+    pub span: Span,
+}
+
+fn get_keywords() -> HashMap<String, TokenType> {
+    let mut keywords = HashMap::new();
+    keywords.insert("and".to_string(), TokenType::And);
+    keywords.insert("class".to_string(), TokenType::Class);
+    keywords.insert("else".to_string(), TokenType::Else);
+    keywords.insert("false".to_string(), TokenType::False);
+    keywords.insert("fun".to_string(), TokenType::Fun);
+    keywords.insert("for".to_string(), TokenType::For);
+    keywords.insert("if".to_string(), TokenType::If);
+    keywords.insert("nil".to_string(), TokenType::Nil);
+    keywords.insert("or".to_string(), TokenType::Or);
+    keywords.insert("print".to_string(), TokenType::Print);
+    keywords.insert("return".to_string(), TokenType::Return);
+    keywords.insert("super".to_string(), TokenType::Super);
+    keywords.insert("this".to_string(), TokenType::This);
+    keywords.insert("true".to_string(), TokenType::True);
+    keywords.insert("var".to_string(), TokenType::Var);
+    keywords.insert("while".to_string(), TokenType::While);
+    keywords
+}
+
+pub struct Scanner {
+    source: String,
+    tokens: Vec<Token>,
+    // Byte offsets into the source. Everything is sliced and spanned by byte, and
+    // only ever advanced a whole character at a time.
+    start: usize,
+    current: usize,
+    line: usize,
+    // Of the current character, counted in characters rather than bytes
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    keywords: HashMap<String, TokenType>,
+    errors: Vec<GloxError>,
+    // Strings with a `${` that hasn't been closed yet, innermost last
+    open_strings: Vec<OpenString>,
+}
+
+// How a string is delimited, which decides how its contents are read
+#[derive(Clone, Copy)]
+struct Quotes {
+    // r"..." has no escapes or interpolation, backslashes are kept as written
+    raw: bool,
+    // """...""" can contain quotes
+    triple: bool,
+}
+
+// A string that's scanning the expression inside a `${...}`. The string carries
+// on at the `}` that brings the brace depth back to zero.
+struct OpenString {
+    quotes: Quotes,
+    // The opening quote, which unterminated string errors point at
+    opening: Span,
+    depth: usize,
+}
+
+impl Scanner {
+    pub fn new(source: String) -> Scanner {
+        let tokens: Vec<Token> = Vec::<Token>::new();
+        let keywords: HashMap<String, TokenType> = get_keywords();
+        Scanner {
+            source,
+            tokens,
+            start: 0,
+            current: 0,
+            line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            keywords,
+            errors: Vec::new(),
+            open_strings: Vec::new(),
+        }
+    }
+
+    // Scans the whole source, carrying on past bad characters so every lexical
+    // error is reported at once
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<GloxError>> {
+        // A byte order mark isn't part of the program, but offsets still count it
+        // so that spans line up with the source as given
+        if self.source.starts_with('\u{feff}') {
+            self.current = '\u{feff}'.len_utf8();
+        }
+        while !self.is_at_end() {
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
+            self.scan_token();
+        }
+        // Only the outermost string is reported, it's the one that needs closing
+        if let Some(open) = self.open_strings.first() {
+            let error = self.unterminated_string(open.opening, open.quotes);
+            self.errors.push(error);
+            self.open_strings.clear();
+        }
+
+        let final_token = Token {
+            token_type: TokenType::EOF,
+            lexeme: "".to_string(),
+            literal: Literal::Nil,
+            span: self.here(),
+        };
+        self.tokens.push(final_token);
+        if self.errors.is_empty() {
+            Ok(self.tokens.clone())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn scan_token(&mut self) {
+        if self.is_at_end() {
+            return;
+        }
+        let c: char = self.advance();
+        match c {
+            // Single character symbols
+            '(' => self.add_token(TokenType::LeftParen),
+            ')' => self.add_token(TokenType::RightParen),
+            '{' => {
+                if let Some(open) = self.open_strings.last_mut() {
+                    open.depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            '}' => match self.open_strings.pop() {
+                Some(open) if open.depth == 0 => self.handle_string(open.quotes, open.opening),
+                Some(mut open) => {
+                    open.depth -= 1;
+                    self.open_strings.push(open);
+                    self.add_token(TokenType::RightBrace)
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
+            ',' => self.add_token(TokenType::Comma),
+            '.' => {
+                let peek: char = self.peek();
+                if self.is_digit(peek) {
+                    self.handle_leading_dot();
+                } else {
+                    self.add_token(TokenType::Dot)
+                }
+            }
+            '-' => self.add_token(TokenType::Minus),
+            '+' => self.add_token(TokenType::Plus),
+            ';' => self.add_token(TokenType::Semicolon),
+            '*' => self.add_token(TokenType::Star),
+            '%' => self.add_token(TokenType::Percent),
+            // One or two character symbols
+            '!' => {
+                if self.check('=') {
+                    self.add_token(TokenType::BangEqual)
+                } else {
+                    self.add_token(TokenType::Bang)
+                }
+            }
+            '=' => {
+                if self.check('=') {
+                    self.add_token(TokenType::EqualEqual)
+                } else {
+                    self.add_token(TokenType::Equal)
+                }
+            }
+            '<' => {
+                if self.check('=') {
+                    self.add_token(TokenType::LessEqual)
+                } else {
+                    self.add_token(TokenType::Less)
+                }
+            }
+            '>' => {
+                if self.check('=') {
+                    self.add_token(TokenType::GreaterEqual)
+                } else {
+                    self.add_token(TokenType::Greater)
+                }
+            }
+            '~' => {
+                if self.check('/') {
+                    self.add_token(TokenType::TildeSlash)
+                } else {
+                    self.errors
+                        .push(GloxError::unexpected_character(c, self.span()));
+                }
+            }
+            // Check for comments
+            '/' => {
+                if self.check('/') {
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
+                    }
+                } else {
+                    self.add_token(TokenType::Slash)
+                }
+            }
+            // Ignore whitespace
+            ' ' => {}
+            '\r' => {}
+            '\t' => {}
+            // New lines
+            '\n' => self.newline(),
+            // String literals
+            '"' => self.open_string(false),
+            // Numbers, identifiers, and keywords
+            _ => {
+                if c == 'r' && self.peek() == '"' {
+                    self.advance();
+                    self.open_string(true);
+                } else if self.is_digit(c) {
+                    self.handle_number(c);
+                } else if self.is_alpha(c) {
+                    self.handle_identifier();
+                } else {
+                    self.errors
+                        .push(GloxError::unexpected_character(c, self.span()));
+                }
+            }
+        }
+    }
+
+    fn advance(&mut self) -> char {
+        // Get the current character then move past all of its bytes
+        let current_char = self.peek();
+        self.current += current_char.len_utf8();
+        self.column += 1;
+        current_char
+    }
+
+    // Called after consuming a newline character
+    fn newline(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    // An empty span at the current character
+    fn here(&self) -> Span {
+        Span {
+            start: self.current,
+            end: self.current,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+            line: self.start_line,
+            column: self.start_column,
+        }
+    }
+
+    fn check(&mut self, c: char) -> bool {
+        if self.is_at_end() || self.peek() != c {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    fn peek(&mut self) -> char {
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    fn peek_next(&mut self) -> char {
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        self.current >= self.source.len()
+    }
+
+    fn is_digit(&mut self, c: char) -> bool {
+        c.is_ascii_digit()
+    }
+
+    // Identifiers follow UAX #31, the same rules as Rust's, so they can be
+    // written in any script: naïve, 変数, π
+    fn is_alpha(&mut self, c: char) -> bool {
+        c == '_' || unicode_ident::is_xid_start(c)
+    }
+
+    fn is_alphanumeric(&mut self, c: char) -> bool {
+        unicode_ident::is_xid_continue(c)
+    }
+
+    fn add_token(&mut self, token_type: TokenType) {
+        self.add_token_literal(token_type, Literal::Nil);
+    }
+
+    // For handling floats and bools
+    fn add_token_literal(&mut self, token_type: TokenType, literal: Literal) {
+        let lexeme = self.source[self.start..self.current].to_string();
+        self.tokens.push(Token {
+            token_type,
+            lexeme,
+            literal,
+            span: self.span(),
+        })
+    }
+
+    // Called after the opening quote, and the r of a raw string
+    fn open_string(&mut self, raw: bool) {
+        let triple = self.peek() == '"' && self.peek_next() == '"';
+        if triple {
+            self.advance();
+            self.advance();
+        }
+        self.handle_string(Quotes { raw, triple }, self.span());
+    }
+
+    // Scans up to the closing quote, or up to a `${` in which case the rest of
+    // the string is scanned once the expression inside has been
+    fn handle_string(&mut self, quotes: Quotes, opening: Span) {
+        let mut value = String::new();
+        loop {
+            // Strings can span multiple lines
+            if self.is_at_end() {
+                let error = self.unterminated_string(opening, quotes);
+                self.errors.push(error);
+                return;
+            }
+            let here: Span = self.here();
+            let c: char = self.advance();
+            match c {
+                '"' if !quotes.triple => break,
+                '"' if self.peek() == '"' && self.peek_next() == '"' => {
+                    self.advance();
+                    self.advance();
+                    break;
+                }
+                '\\' if !quotes.raw => {
+                    if let Some(escaped) = self.escape(here) {
+                        value.push(escaped);
+                    }
+                }
+                '$' if !quotes.raw && self.peek() == '{' => {
+                    self.advance();
+                    self.add_token_literal(TokenType::Interpolation, Literal::Str(value));
+                    self.open_strings.push(OpenString {
+                        quotes,
+                        opening,
+                        depth: 0,
+                    });
+                    return;
+                }
+                // Line breaks in a string are \n whatever the file uses
+                '\r' if self.peek() == '\n' => {}
+                '\n' => {
+                    self.newline();
+                    value.push(c);
+                }
+                _ => value.push(c),
+            }
+        }
+        self.add_token_literal(TokenType::String, Literal::Str(value));
+    }
+
+    // Called after a backslash, which starts at the given span
+    fn escape(&mut self, mut span: Span) -> Option<char> {
+        // An unterminated string, which handle_string reports
+        if self.is_at_end() {
+            return None;
+        }
+        let escaped = match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '$' => Some('$'),
+            'u' => {
+                let escaped = self.unicode_escape();
+                if escaped.is_none() {
+                    span.end = self.current;
+                    self.errors.push(
+                        GloxError::invalid_escape("Invalid unicode escape.", span).with_help(
+                            "write the code point in hex between braces, as in \\u{1F600}",
+                        ),
+                    );
+                }
+                return escaped;
+            }
+            // Newlines still need counting
+            '\n' => {
+                self.newline();
+                None
+            }
+            _ => None,
+        };
+        if escaped.is_none() {
+            span.end = self.current;
+            let sequence = &self.source[span.start..span.end];
+            self.errors.push(
+                GloxError::invalid_escape(format!("Invalid escape sequence '{}'.", sequence), span)
+                    .with_help(
+                        "the escapes are \\n \\t \\r \\0 \\\\ \\\" \\$ and \\u{...}, \
+                         or use a raw string r\"...\" to keep backslashes as written",
+                    ),
+            );
+        }
+        escaped
+    }
+
+    // The {...} of a \u{...} escape, one to six hex digits naming a code point
+    fn unicode_escape(&mut self) -> Option<char> {
+        if self.peek() != '{' {
+            return None;
+        }
+        self.advance();
+        let start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let end = self.current;
+        if self.peek() != '}' {
+            return None;
+        }
+        self.advance();
+        let digits = &self.source[start..end];
+        if digits.is_empty() || digits.len() > 6 {
+            return None;
+        }
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+    }
+
+    fn unterminated_string(&self, opening: Span, quotes: Quotes) -> GloxError {
+        let closing = if quotes.triple { "\"\"\"" } else { "\"" };
+        GloxError::unterminated_string(opening)
+            .with_help(format!("add a closing '{}' to end the string", closing))
+    }
+
+    // Numbers are decimal (with an optional fraction and exponent, 1.5e-3), or ints
+    // in hexadecimal (0xFF), binary (0b1010) or octal (0o17). Underscores can go
+    // between digits to group them, as in 1_000_000.
+    fn handle_number(&mut self, first: char) {
+        let radix = match self.peek() {
+            'x' | 'X' if first == '0' => Some((16, "a hexadecimal")),
+            'b' | 'B' if first == '0' => Some((2, "a binary")),
+            'o' | 'O' if first == '0' => Some((8, "an octal")),
+            _ => None,
+        };
+        if let Some((radix, kind)) = radix {
+            self.advance();
+            let Some(digits) = self.digits(self.current, radix, kind) else {
+                return;
+            };
+            match i64::from_str_radix(&digits, radix) {
+                Ok(int_value) => self.add_token_literal(TokenType::Number, Literal::Int(int_value)),
+                Err(_) => self.int_too_large(),
+            }
+            return;
+        }
+
+        // The first digit has already been consumed
+        let Some(mut number) = self.digits(self.start, 10, "a decimal") else {
+            return;
+        };
+        let mut is_float = false;
+
+        // Check for a decimal and keep going if numbers follow it
+        let peek_next: char = self.peek_next();
+        if self.peek() == '.' && self.is_digit(peek_next) {
+            is_float = true;
+            self.advance();
+            let Some(fraction) = self.digits(self.current, 10, "a decimal") else {
+                return;
+            };
+            number.push('.');
+            number.push_str(&fraction);
+        }
+
+        if matches!(self.peek(), 'e' | 'E') {
+            is_float = true;
+            self.advance();
+            number.push('e');
+            if matches!(self.peek(), '+' | '-') {
+                number.push(self.advance());
+            }
+            let Some(exponent) = self.digits(self.current, 10, "a decimal") else {
+                return;
+            };
+            number.push_str(&exponent);
+        }
+
+        if is_float {
+            let float_value = number.parse::<f64>().unwrap();
+            if float_value.is_infinite() {
+                self.errors.push(GloxError::invalid_number(
+                    "Float literal is too large.",
+                    self.span(),
+                ));
+                return;
+            }
+            self.add_token_literal(TokenType::Number, Literal::Float(float_value));
+            return;
+        }
+        match number.parse::<i64>() {
+            Ok(int_value) => self.add_token_literal(TokenType::Number, Literal::Int(int_value)),
+            Err(_) => self.int_too_large(),
+        }
+    }
+
+    // Consumes the digits of a number from start, which may already have been
+    // consumed, up to the current character. Letters are swallowed too so that a
+    // typo like 0b102 or 12px is reported as one bad number. Returns the digits
+    // without underscores, or None after reporting an error.
+    fn digits(&mut self, start: usize, radix: u32, kind: &str) -> Option<String> {
+        let mut peek: char = self.peek();
+        while peek.is_ascii_alphanumeric() || peek == '_' {
+            // The exponent of a decimal is scanned separately
+            if radix == 10 && matches!(peek, 'e' | 'E') {
+                break;
+            }
+            self.advance();
+            peek = self.peek();
+        }
+
+        let text = &self.source[start..self.current];
+        let error = if text.is_empty() {
+            format!(
+                "Expected digits after '{}'.",
+                &self.source[self.start..start]
+            )
+        } else if let Some(c) = text.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+            format!("Invalid digit '{}' for {} number.", c, kind)
+        } else if text.starts_with('_') || text.ends_with('_') || text.contains("__") {
+            "Underscores in a number must go between digits.".to_string()
+        } else {
+            return Some(text.replace('_', ""));
+        };
+        self.errors
+            .push(GloxError::invalid_number(error, self.span()));
+        None
+    }
+
+    fn int_too_large(&mut self) {
+        self.errors.push(
+            GloxError::invalid_number("Integer literal is too large.", self.span()).with_help(
+                format!(
+                    "the largest int is {}, add '.0' to make it a float",
+                    i64::MAX
+                ),
+            ),
+        );
+    }
+
+    // Called on a '.' followed by a digit, which is a mistake rather than a
+    // property access since property names can't start with a digit
+    fn handle_leading_dot(&mut self) {
+        let mut peek: char = self.peek();
+        while peek.is_ascii_alphanumeric() || peek == '_' {
+            self.advance();
+            peek = self.peek();
+        }
+        let number = &self.source[self.start..self.current];
+        let help = format!("add a zero before the '.', as in 0{}", number);
+        self.errors.push(
+            GloxError::invalid_number("Numbers can't start with '.'.", self.span()).with_help(help),
+        );
+    }
+
+    fn handle_identifier(&mut self) {
+        let mut peek: char = self.peek();
+        while self.is_alphanumeric(peek) {
+            self.advance();
+            peek = self.peek();
+        }
+        let text: String = self.source[self.start..self.current].to_string();
+        // Try to match the text to a keyword otherwise it's an Identifier
+        let token_type: TokenType = self
+            .keywords
+            .get(&text)
+            .copied()
+            .unwrap_or(TokenType::Identifier);
+        self.add_token(token_type);
+    }
+}
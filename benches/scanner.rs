@@ -0,0 +1,119 @@
+// Throughput of the front end on a large generated program, the kind of file the
+// scanner has to keep up with. Run with `cargo bench --bench scanner`.
+//
+// On a 10 MB program (2.7M tokens), release build on one machine:
+//
+//   scan         114.69 ms     87.4 MB/s   23.5 M tokens/s
+//   tokenize     219.77 ms     45.6 MB/s   12.3 M tokens/s
+//   eager        540.60 ms     18.5 MB/s    5.0 M tokens/s
+//   check        723.30 ms     13.9 MB/s    3.7 M tokens/s
+//
+// eager is tokenize as it was before the scanner went lazy over the borrowed
+// source, with the old scanner kept in eager_scanner as the baseline. Both come
+// from the same run, so the comparison holds on whatever machine it's run on.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use glox::Glox;
+
+mod eager_scanner;
+
+// How long each measurement runs for, after a warm up run
+const DURATION: Duration = Duration::from_secs(3);
+
+fn main() {
+    let source = program(20_000);
+    let tokens = Glox::tokenize(&source).unwrap();
+    // The baseline has to scan the same tokens for the comparison to mean anything
+    let eager = eager_scanner::Scanner::new(source.clone())
+        .scan_tokens()
+        .unwrap();
+    assert_eq!(eager.len(), tokens.len());
+    for (old, new) in eager.iter().zip(&tokens) {
+        assert_eq!(
+            (old.token_type, old.lexeme.as_str(), old.span),
+            (new.token_type, new.lexeme, new.span)
+        );
+        assert_eq!(old.literal.to_string(), new.literal.to_string());
+    }
+    let tokens = tokens.len();
+    println!(
+        "{:.1} MB of source, {} tokens",
+        source.len() as f64 / 1e6,
+        tokens
+    );
+
+    measure("scan", &source, tokens, |s| {
+        for token in Glox::tokens(s) {
+            black_box(token.unwrap());
+        }
+    });
+    measure("tokenize", &source, tokens, |s| {
+        black_box(Glox::tokenize(s).unwrap());
+    });
+    measure("eager", &source, tokens, |s| {
+        black_box(
+            eager_scanner::Scanner::new(s.to_string())
+                .scan_tokens()
+                .unwrap(),
+        );
+    });
+    measure("check", &source, tokens, |s| {
+        Glox::check(black_box(s)).unwrap();
+    });
+}
+
+fn measure(name: &str, source: &str, tokens: usize, run: impl Fn(&str)) {
+    run(source);
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < DURATION {
+        run(source);
+        runs += 1;
+    }
+    let seconds = start.elapsed().as_secs_f64() / runs as f64;
+    println!(
+        "{:<10} {:>8.2} ms {:>8.1} MB/s {:>6.1} M tokens/s",
+        name,
+        seconds * 1e3,
+        source.len() as f64 / 1e6 / seconds,
+        tokens as f64 / 1e6 / seconds
+    );
+}
+
+// Classes and functions with a bit of everything the scanner handles: keywords,
+// identifiers, numbers, strings and comments
+fn program(functions: usize) -> String {
+    let mut source = String::new();
+    for i in 0..functions {
+        source.push_str(&format!(
+            "// Generated function number {i}
+class Point{i} {{
+  init(x, y) {{
+    this.x = x;
+    this.y = y;
+  }}
+
+  scaled(factor) {{
+    return Point{i}(this.x * factor, this.y * factor);
+  }}
+}}
+
+fun step{i}(count, label) {{
+  var total = 0.5;
+  for (var n = 0; n < count; n = n + 1) {{
+    if (n % 3 == 0 and n != {i}) {{
+      total = total + n * 1.25 - 0x1F;
+    }} else {{
+      total = total - 1_000;
+    }}
+  }}
+  var message = \"step {i} of \" + label;
+  return Point{i}(total, len(message)).scaled(2);
+}}
+
+"
+        ));
+    }
+    source
+}
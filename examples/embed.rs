@@ -0,0 +1,37 @@
+// Runs a Lox script from Rust, sharing globals and host functions with it.
+//
+//     cargo run --example embed
+use glox::{Glox, Value};
+
+fn main() {
+    let mut glox = Glox::new();
+    glox.set_global("limit", Value::Float(3.0));
+    glox.register("double", |x: f64| x * 2.0);
+    glox.register("shout", |s: String| {
+        if s.is_empty() {
+            Err("nothing to shout")
+        } else {
+            Ok(s.to_uppercase())
+        }
+    });
+
+    let source = "
+        var total = 0;
+        for (var i = 0; i < limit; i = i + 1) {
+          total = total + double(i);
+        }
+        print shout(\"done\");
+        double(\"oops\");
+    ";
+    if let Err(errors) = glox.run(source) {
+        for e in errors {
+            eprintln!("{}", e.render(source, "<script>"));
+        }
+    }
+
+    println!("total = {}", glox.get_global("total").unwrap());
+    match glox.eval("shout(\"\")") {
+        Ok(value) => println!("{}", value),
+        Err(errors) => eprintln!("{}", errors[0]),
+    }
+}
@@ -0,0 +1,236 @@
+use std::rc::Rc;
+
+use crate::expr::{
+    Assign, Binary, Call, Expr, Get, Grouping, Index, Interpolate, ListExpr, LiteralExpr, Logical,
+    MapExpr, Set, SetIndex, Super, This, Unary, Variable, Visitor,
+};
+use crate::stmt::{
+    BlockStmt, ClassStmt, ExpressionStmt, ForStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt,
+    Stmt, StmtVisitor, VarStmt, WhileStmt,
+};
+use crate::token::Literal;
+
+// Prints the tree as nested s-expressions, e.g. `(print (+ 1 (* 2 3)))`, so it's
+// easy to see how the parser grouped things. Statements go on their own lines,
+// with the bodies of blocks, functions and classes indented beneath them.
+pub struct AstPrinter {
+    indent: usize,
+}
+
+impl AstPrinter {
+    pub fn new() -> AstPrinter {
+        AstPrinter { indent: 0 }
+    }
+
+    pub fn print(&mut self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|s| self.print_stmt(s))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn print_expr(&mut self, expr: &Expr) -> String {
+        expr.accept(self)
+    }
+
+    fn print_stmt(&mut self, stmt: &Stmt) -> String {
+        format!("{}{}", "  ".repeat(self.indent), stmt.accept(self))
+    }
+
+    fn parenthesize(&mut self, name: &str, exprs: &[&Expr]) -> String {
+        let mut out = format!("({}", name);
+        for expr in exprs {
+            out.push(' ');
+            out.push_str(&expr.accept(self));
+        }
+        out.push(')');
+        out
+    }
+
+    // Prints the statements one per line, one level deeper than the current one
+    fn print_body(&mut self, statements: &[Stmt]) -> String {
+        self.indent += 1;
+        let body = statements
+            .iter()
+            .map(|s| format!("\n{}", self.print_stmt(s)))
+            .collect::<String>();
+        self.indent -= 1;
+        body
+    }
+
+    fn print_function(&mut self, keyword: &str, function: &FunctionStmt) -> String {
+        let params: Vec<&str> = function.params.iter().map(|p| &*p.lexeme).collect();
+        format!(
+            "({} {} ({}){})",
+            keyword,
+            function.name.lexeme,
+            params.join(" "),
+            self.print_body(&function.body)
+        )
+    }
+}
+
+impl Visitor<String> for AstPrinter {
+    fn visit_literal(&mut self, expr: &LiteralExpr) -> String {
+        match &expr.value {
+            // Quoted so `"1"` and `1` can be told apart
+            Literal::Str(s) => format!("{:?}", s),
+            value => value.to_string(),
+        }
+    }
+
+    fn visit_grouping(&mut self, expr: &Grouping) -> String {
+        self.parenthesize("group", &[&expr.expression])
+    }
+
+    fn visit_unary(&mut self, expr: &Unary) -> String {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.right])
+    }
+
+    fn visit_binary(&mut self, expr: &Binary) -> String {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) -> String {
+        expr.name.lexeme.to_string()
+    }
+
+    fn visit_assign(&mut self, expr: &Assign) -> String {
+        self.parenthesize(&format!("= {}", expr.name.lexeme), &[&expr.value])
+    }
+
+    fn visit_logical(&mut self, expr: &Logical) -> String {
+        self.parenthesize(&expr.operator.lexeme, &[&expr.left, &expr.right])
+    }
+
+    fn visit_call(&mut self, expr: &Call) -> String {
+        let mut exprs: Vec<&Expr> = vec![&expr.callee];
+        exprs.extend(&expr.arguments);
+        self.parenthesize("call", &exprs)
+    }
+
+    fn visit_get(&mut self, expr: &Get) -> String {
+        self.parenthesize(&format!(". {}", expr.name.lexeme), &[&expr.object])
+    }
+
+    fn visit_set(&mut self, expr: &Set) -> String {
+        self.parenthesize(
+            &format!(".= {}", expr.name.lexeme),
+            &[&expr.object, &expr.value],
+        )
+    }
+
+    fn visit_this(&mut self, _expr: &This) -> String {
+        "this".to_string()
+    }
+
+    fn visit_super(&mut self, expr: &Super) -> String {
+        format!("(super {})", expr.method.lexeme)
+    }
+
+    fn visit_list(&mut self, expr: &ListExpr) -> String {
+        let elements: Vec<&Expr> = expr.elements.iter().collect();
+        self.parenthesize("list", &elements)
+    }
+
+    fn visit_map(&mut self, expr: &MapExpr) -> String {
+        let entries: Vec<&Expr> = expr.entries.iter().flat_map(|(k, v)| [k, v]).collect();
+        self.parenthesize("map", &entries)
+    }
+
+    fn visit_index(&mut self, expr: &Index) -> String {
+        self.parenthesize("[]", &[&expr.object, &expr.index])
+    }
+
+    fn visit_set_index(&mut self, expr: &SetIndex) -> String {
+        self.parenthesize("[]=", &[&expr.object, &expr.index, &expr.value])
+    }
+
+    fn visit_interpolate(&mut self, expr: &Interpolate) -> String {
+        let parts: Vec<&Expr> = expr.parts.iter().collect();
+        self.parenthesize("interpolate", &parts)
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) -> String {
+        self.parenthesize(";", &[&stmt.expression])
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> String {
+        self.parenthesize("print", &[&stmt.expression])
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> String {
+        let name = format!("var {}", stmt.name.lexeme);
+        match &stmt.initializer {
+            Some(initializer) => self.parenthesize(&name, &[initializer]),
+            None => format!("({})", name),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> String {
+        format!("(block{})", self.print_body(&stmt.statements))
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> String {
+        let condition = stmt.condition.accept(self);
+        let mut branches = self.print_body(std::slice::from_ref(&*stmt.then_branch));
+        if let Some(else_branch) = &stmt.else_branch {
+            branches.push_str(&self.print_body(std::slice::from_ref(&**else_branch)));
+        }
+        format!("(if {}{})", condition, branches)
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> String {
+        let condition = stmt.condition.accept(self);
+        let body = self.print_body(std::slice::from_ref(&*stmt.body));
+        format!("(while {}{})", condition, body)
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &ForStmt) -> String {
+        let mut clauses: Vec<String> = Vec::new();
+        clauses.push(match &stmt.initializer {
+            Some(initializer) => initializer.accept(self),
+            None => "()".to_string(),
+        });
+        for clause in [&stmt.condition, &stmt.increment] {
+            clauses.push(match clause {
+                Some(expr) => expr.accept(self),
+                None => "()".to_string(),
+            });
+        }
+        let body = self.print_body(std::slice::from_ref(&*stmt.body));
+        format!("(for {}{})", clauses.join(" "), body)
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<FunctionStmt>) -> String {
+        self.print_function("fun", stmt)
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> String {
+        match &stmt.value {
+            Some(value) => self.parenthesize("return", &[value]),
+            None => "(return)".to_string(),
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> String {
+        let mut out = format!("(class {}", stmt.name.lexeme);
+        if let Some(superclass) = &stmt.superclass {
+            out.push_str(&format!(" < {}", superclass.name.lexeme));
+        }
+        self.indent += 1;
+        for method in &stmt.methods {
+            out.push_str(&format!(
+                "\n{}{}",
+                "  ".repeat(self.indent),
+                self.print_function("method", method)
+            ));
+        }
+        self.indent -= 1;
+        out.push(')');
+        out
+    }
+}
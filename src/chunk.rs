@@ -0,0 +1,289 @@
+use std::fmt;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::object::{Quoted, Value};
+use crate::token::Span;
+
+// Defines OpCode along with the table from_byte decodes with, so the two can't
+// get out of step
+macro_rules! opcodes {
+    ($($(#[$comment:meta])* $name:ident,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum OpCode {
+            $($(#[$comment])* $name,)*
+        }
+
+        const OPCODES: &[OpCode] = &[$(OpCode::$name,)*];
+    };
+}
+
+// The instructions of the VM. Operands follow the opcode in the code, a "constant"
+// operand is a u16 index into the chunk's constants and a "slot" is a u8.
+// Instructions that can fail have the span of the code they came from in the
+// chunk's span table, some have more than one for the different errors.
+opcodes! {
+    // constant
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    // slot, counted from the frame's first slot
+    GetLocal,
+    SetLocal,
+    // constant naming the variable
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    // slot in the closure's upvalues
+    GetUpvalue,
+    SetUpvalue,
+    // constant naming the property
+    GetProperty,
+    SetProperty,
+    // constant naming the method, the superclass is on top of the stack
+    GetSuper,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    IntegerDivide,
+    Modulo,
+    Not,
+    Negate,
+    Print,
+    // u16 offset forwards from the end of the instruction
+    Jump,
+    JumpIfFalse,
+    // u16 offset backwards from the end of the instruction
+    Loop,
+    // u8 argument count. Spans: the whole call, the callee.
+    Call,
+    // constant naming the method. Looks it up on the instance on top of the
+    // stack and pushes it, so a missing method is an error before the arguments
+    // of the Invoke that follows run.
+    GetMethod,
+    // Like GetMethod, with the superclass on top of the stack in place of it
+    GetSuperMethod,
+    // u8 argument count, calls what a GetMethod looked up with the instance it
+    // was looked up on as the receiver. Spans: the whole call, the callee.
+    Invoke,
+    // u16 index into the chunk's functions, then a pair of bytes for each of its
+    // upvalues: 1 to capture a local of the enclosing function, 0 for one of its
+    // upvalues, and the slot
+    Closure,
+    CloseUpvalue,
+    Return,
+    // constant naming the class
+    Class,
+    // Copies the methods of the superclass below the class into it
+    Inherit,
+    // constant naming the method, the closure is on top of the class
+    Method,
+    // u16 element count
+    List,
+    // u16 entry count, keys and values alternate on the stack. Spans: the literal,
+    // then each key.
+    Map,
+    // Spans: the index, the object
+    Index,
+    SetIndex,
+    // u16 part count, joins the parts of an interpolated string as they'd print
+    Interpolate,
+}
+
+impl OpCode {
+    pub fn from_byte(byte: u8) -> OpCode {
+        OPCODES[byte as usize]
+    }
+}
+
+// A compiled sequence of instructions along with the values they refer to
+#[derive(Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Value>,
+    // The functions declared directly inside this one, see OpCode::Closure
+    pub functions: Vec<Rc<Function>>,
+    // The span table, the offset of each instruction that has spans followed by
+    // one of them, in code order
+    spans: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn write_op(&mut self, op: OpCode, span: Span) {
+        self.spans.push((self.code.len(), span));
+        self.code.push(op as u8);
+    }
+
+    // Adds another span to the last instruction written
+    pub fn add_span(&mut self, span: Span) {
+        let offset = self.spans.last().map_or(0, |(offset, _)| *offset);
+        self.spans.push((offset, span));
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.code.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    // The nth span of the instruction at offset, or the first if it has fewer
+    pub fn span(&self, offset: usize, n: usize) -> Span {
+        let first = self.spans.partition_point(|(o, _)| *o < offset);
+        let spans = &self.spans[first..];
+        let count = spans.iter().take_while(|(o, _)| *o == offset).count();
+        match count {
+            0 => Span::default(),
+            count => spans[n.min(count - 1)].1,
+        }
+    }
+}
+
+// A function compiled to bytecode. The top level of a script is one too, without
+// a name.
+#[derive(Default)]
+pub struct Function {
+    pub name: Option<Rc<str>>,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+impl Function {
+    // Lists the instructions of the function, then of every function inside it
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+        self.disassemble_into(&mut out);
+        out
+    }
+
+    fn disassemble_into(&self, out: &mut String) {
+        let chunk = &self.chunk;
+        let _ = writeln!(out, "== {} ==", self);
+        let mut offset = 0;
+        let mut line = 0;
+        while offset < chunk.code.len() {
+            let span_line = chunk.span(offset, 0).line;
+            if span_line == line {
+                let _ = write!(out, "{:04}    | ", offset);
+            } else {
+                let _ = write!(out, "{:04} {:>4} ", offset, span_line);
+                line = span_line;
+            }
+            offset = self.disassemble_instruction(out, offset);
+        }
+        for function in &chunk.functions {
+            out.push('\n');
+            function.disassemble_into(out);
+        }
+    }
+
+    // Writes one line for the instruction at offset and returns where the next starts
+    fn disassemble_instruction(&self, out: &mut String, offset: usize) -> usize {
+        let chunk = &self.chunk;
+        let op = OpCode::from_byte(chunk.code[offset]);
+        let name = op_name(op);
+        let constant = |at: usize| {
+            let index = chunk.read_u16(at);
+            (index, Quoted(&chunk.constants[index as usize]).to_string())
+        };
+        match op {
+            OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::GetMethod
+            | OpCode::GetSuperMethod
+            | OpCode::Class
+            | OpCode::Method => {
+                let (index, value) = constant(offset + 1);
+                let _ = writeln!(out, "{:<16} {:4} {}", name, index, value);
+                offset + 3
+            }
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::Call
+            | OpCode::Invoke => {
+                let _ = writeln!(out, "{:<16} {:4}", name, chunk.code[offset + 1]);
+                offset + 2
+            }
+            OpCode::List | OpCode::Map | OpCode::Interpolate => {
+                let _ = writeln!(out, "{:<16} {:4}", name, chunk.read_u16(offset + 1));
+                offset + 3
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+                let jump = chunk.read_u16(offset + 1) as usize;
+                let target = if op == OpCode::Loop {
+                    offset + 3 - jump
+                } else {
+                    offset + 3 + jump
+                };
+                let _ = writeln!(out, "{:<16} {:4} -> {}", name, offset, target);
+                offset + 3
+            }
+            OpCode::Closure => {
+                let index = chunk.read_u16(offset + 1) as usize;
+                let function = &chunk.functions[index];
+                let _ = writeln!(out, "{:<16} {:4} {}", name, index, function);
+                let mut offset = offset + 3;
+                for _ in 0..function.upvalue_count {
+                    let kind = if chunk.code[offset] == 1 {
+                        "local"
+                    } else {
+                        "upvalue"
+                    };
+                    let _ = writeln!(
+                        out,
+                        "{:04}      |                     {} {}",
+                        offset,
+                        kind,
+                        chunk.code[offset + 1]
+                    );
+                    offset += 2;
+                }
+                offset
+            }
+            _ => {
+                let _ = writeln!(out, "{}", name);
+                offset + 1
+            }
+        }
+    }
+}
+
+// GetLocal is shown as OP_GET_LOCAL, like clox does
+fn op_name(op: OpCode) -> String {
+    let mut name = String::from("OP");
+    for c in format!("{:?}", op).chars() {
+        if c.is_uppercase() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    name
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<script>"),
+        }
+    }
+}
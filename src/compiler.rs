@@ -0,0 +1,708 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::chunk::{Chunk, Function, OpCode};
+use crate::error::GloxError;
+use crate::expr::{
+    Assign, Binary, Call, Expr, Get, Grouping, Index, Interpolate, ListExpr, LiteralExpr, Logical,
+    MapExpr, Set, SetIndex, Super, This, Unary, Variable, Visitor,
+};
+use crate::object::Value;
+use crate::stmt::{
+    BlockStmt, ClassStmt, ExpressionStmt, ForStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt,
+    Stmt, StmtVisitor, VarStmt, WhileStmt,
+};
+use crate::token::{Literal, Span, TokenType};
+
+// Slots and upvalue indices are a single byte in the code
+const LOCALS_MAX: usize = u8::MAX as usize + 1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: Rc<str>,
+    depth: usize,
+    // Captured locals are moved off the stack when they go out of scope,
+    // rather than just popped
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    index: u8,
+    is_local: bool,
+}
+
+// The function being compiled, one for each function the compiler is inside of
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    // Names and strings already in the constant pool, so each is only added once
+    strings: HashMap<Rc<str>, u16>,
+}
+
+impl FunctionState {
+    fn new(name: Option<Rc<str>>, kind: FunctionKind) -> FunctionState {
+        // Slot 0 holds the function being called, or `this` in a method
+        let slot_zero = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            FunctionKind::Script | FunctionKind::Function => "",
+        };
+        FunctionState {
+            function: Function {
+                name,
+                ..Function::default()
+            },
+            kind,
+            locals: vec![Local {
+                name: Rc::from(slot_zero),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            strings: HashMap::new(),
+        }
+    }
+}
+
+// Compiles a resolved program into bytecode for the Vm, in a single pass over
+// the tree. The resolver has already reported misuses like `return` at the top
+// level, so the only errors left are programs too big for the bytecode's operands.
+pub struct Compiler {
+    // The innermost function is last
+    functions: Vec<FunctionState>,
+    errors: Vec<GloxError>,
+    // The span of the last instruction, for the ones that don't come from any
+    // particular piece of code, like the return at the end of a function
+    previous: Span,
+}
+
+impl Visitor<()> for Compiler {
+    fn visit_literal(&mut self, expr: &LiteralExpr) {
+        match &expr.value {
+            Literal::Nil => self.emit_op(OpCode::Nil, expr.span),
+            Literal::Bool(true) => self.emit_op(OpCode::True, expr.span),
+            Literal::Bool(false) => self.emit_op(OpCode::False, expr.span),
+            Literal::Str(s) => {
+                let constant = self.string_constant(s, expr.span);
+                self.emit_with_u16(OpCode::Constant, constant, expr.span);
+            }
+            value => {
+                let constant = self.make_constant(Value::from(value), expr.span);
+                self.emit_with_u16(OpCode::Constant, constant, expr.span);
+            }
+        }
+    }
+
+    fn visit_grouping(&mut self, expr: &Grouping) {
+        self.compile_expr(&expr.expression);
+    }
+
+    fn visit_unary(&mut self, expr: &Unary) {
+        self.compile_expr(&expr.right);
+        let span = expr.operator.span.to(expr.right.span());
+        match expr.operator.token_type {
+            TokenType::Minus => self.emit_op(OpCode::Negate, span),
+            _ => self.emit_op(OpCode::Not, span),
+        }
+    }
+
+    fn visit_binary(&mut self, expr: &Binary) {
+        self.compile_expr(&expr.left);
+        self.compile_expr(&expr.right);
+        let op = match expr.operator.token_type {
+            TokenType::BangEqual => OpCode::NotEqual,
+            TokenType::EqualEqual => OpCode::Equal,
+            TokenType::Greater => OpCode::Greater,
+            TokenType::GreaterEqual => OpCode::GreaterEqual,
+            TokenType::Less => OpCode::Less,
+            TokenType::LessEqual => OpCode::LessEqual,
+            TokenType::Plus => OpCode::Add,
+            TokenType::Minus => OpCode::Subtract,
+            TokenType::Star => OpCode::Multiply,
+            TokenType::Slash => OpCode::Divide,
+            TokenType::TildeSlash => OpCode::IntegerDivide,
+            _ => OpCode::Modulo,
+        };
+        self.emit_op(op, expr.left.span().to(expr.right.span()));
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) {
+        self.get_variable(&expr.name.lexeme, expr.name.span);
+    }
+
+    fn visit_assign(&mut self, expr: &Assign) {
+        self.compile_expr(&expr.value);
+        let span = expr.name.span;
+        let name = &expr.name.lexeme;
+        let last = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(last, name) {
+            self.emit_with_byte(OpCode::SetLocal, slot, span);
+        } else if let Some(index) = self.resolve_upvalue(last, name, span) {
+            self.emit_with_byte(OpCode::SetUpvalue, index, span);
+        } else {
+            let constant = self.string_constant(name, span);
+            self.emit_with_u16(OpCode::SetGlobal, constant, span);
+        }
+    }
+
+    // The left operand stays on the stack as the result when it decides it
+    fn visit_logical(&mut self, expr: &Logical) {
+        let span = expr.operator.span;
+        self.compile_expr(&expr.left);
+        if expr.operator.token_type == TokenType::And {
+            let end_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+            self.emit_op(OpCode::Pop, span);
+            self.compile_expr(&expr.right);
+            self.patch_jump(end_jump, span);
+        } else {
+            let else_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+            let end_jump = self.emit_jump(OpCode::Jump, span);
+            self.patch_jump(else_jump, span);
+            self.emit_op(OpCode::Pop, span);
+            self.compile_expr(&expr.right);
+            self.patch_jump(end_jump, span);
+        }
+    }
+
+    // Calling a method straight off an object or `super` looks the method up
+    // without creating the bound method a separate get would, before the
+    // arguments run like they would after a get
+    fn visit_call(&mut self, expr: &Call) {
+        let span = expr.callee.span().to(expr.paren.span);
+        let arguments = expr.arguments.len() as u8;
+        match &*expr.callee {
+            Expr::Get(get) => {
+                self.compile_expr(&get.object);
+                let constant = self.string_constant(&get.name.lexeme, get.name.span);
+                self.emit_with_u16(OpCode::GetMethod, constant, get.name.span);
+                self.compile_exprs(&expr.arguments);
+                self.emit_with_byte(OpCode::Invoke, arguments, span);
+                self.chunk().add_span(expr.callee.span());
+            }
+            Expr::Super(sup) => {
+                self.get_variable("this", sup.keyword.span);
+                self.get_variable("super", sup.keyword.span);
+                let constant = self.string_constant(&sup.method.lexeme, sup.method.span);
+                self.emit_with_u16(OpCode::GetSuperMethod, constant, sup.method.span);
+                self.compile_exprs(&expr.arguments);
+                self.emit_with_byte(OpCode::Invoke, arguments, span);
+                self.chunk().add_span(expr.callee.span());
+            }
+            callee => {
+                self.compile_expr(callee);
+                self.compile_exprs(&expr.arguments);
+                self.emit_with_byte(OpCode::Call, arguments, span);
+                self.chunk().add_span(callee.span());
+            }
+        }
+    }
+
+    fn visit_get(&mut self, expr: &Get) {
+        self.compile_expr(&expr.object);
+        let constant = self.string_constant(&expr.name.lexeme, expr.name.span);
+        self.emit_with_u16(OpCode::GetProperty, constant, expr.name.span);
+    }
+
+    fn visit_set(&mut self, expr: &Set) {
+        self.compile_expr(&expr.object);
+        self.compile_expr(&expr.value);
+        let constant = self.string_constant(&expr.name.lexeme, expr.name.span);
+        self.emit_with_u16(OpCode::SetProperty, constant, expr.name.span);
+    }
+
+    fn visit_this(&mut self, expr: &This) {
+        self.get_variable("this", expr.keyword.span);
+    }
+
+    fn visit_super(&mut self, expr: &Super) {
+        self.get_variable("this", expr.keyword.span);
+        self.get_variable("super", expr.keyword.span);
+        let constant = self.string_constant(&expr.method.lexeme, expr.method.span);
+        self.emit_with_u16(OpCode::GetSuper, constant, expr.method.span);
+    }
+
+    fn visit_list(&mut self, expr: &ListExpr) {
+        self.compile_exprs(&expr.elements);
+        let count = self.count(
+            expr.elements.len(),
+            "Too many elements in a list.",
+            expr.span,
+        );
+        self.emit_with_u16(OpCode::List, count, expr.span);
+    }
+
+    fn visit_map(&mut self, expr: &MapExpr) {
+        for (key, value) in &expr.entries {
+            self.compile_expr(key);
+            self.compile_expr(value);
+        }
+        let count = self.count(expr.entries.len(), "Too many entries in a map.", expr.span);
+        self.emit_with_u16(OpCode::Map, count, expr.span);
+        for (key, _) in &expr.entries {
+            self.chunk().add_span(key.span());
+        }
+    }
+
+    fn visit_index(&mut self, expr: &Index) {
+        self.compile_expr(&expr.object);
+        self.compile_expr(&expr.index);
+        self.emit_op(OpCode::Index, expr.index.span());
+        self.chunk().add_span(expr.object.span());
+    }
+
+    fn visit_set_index(&mut self, expr: &SetIndex) {
+        self.compile_expr(&expr.object);
+        self.compile_expr(&expr.index);
+        self.compile_expr(&expr.value);
+        self.emit_op(OpCode::SetIndex, expr.index.span());
+        self.chunk().add_span(expr.object.span());
+    }
+
+    fn visit_interpolate(&mut self, expr: &Interpolate) {
+        self.compile_exprs(&expr.parts);
+        let count = self.count(
+            expr.parts.len(),
+            "Too many parts in an interpolated string.",
+            expr.span,
+        );
+        self.emit_with_u16(OpCode::Interpolate, count, expr.span);
+    }
+}
+
+impl StmtVisitor<()> for Compiler {
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) {
+        self.compile_expr(&stmt.expression);
+        self.emit_op(OpCode::Pop, stmt.expression.span());
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        self.compile_expr(&stmt.expression);
+        self.emit_op(OpCode::Print, stmt.expression.span());
+    }
+
+    // A local's value is left on the stack, in the slot it was given
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) {
+        match &stmt.initializer {
+            Some(initializer) => self.compile_expr(initializer),
+            None => self.emit_op(OpCode::Nil, stmt.name.span),
+        }
+        self.define_variable(&stmt.name.lexeme, stmt.name.span);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) {
+        self.begin_scope();
+        self.compile(&stmt.statements);
+        self.end_scope();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) {
+        let span = stmt.condition.span();
+        self.compile_expr(&stmt.condition);
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+        self.emit_op(OpCode::Pop, span);
+        self.compile_stmt(&stmt.then_branch);
+        let else_jump = self.emit_jump(OpCode::Jump, span);
+        self.patch_jump(then_jump, span);
+        self.emit_op(OpCode::Pop, span);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.compile_stmt(else_branch);
+        }
+        self.patch_jump(else_jump, span);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) {
+        let span = stmt.condition.span();
+        let loop_start = self.chunk().code.len();
+        self.compile_expr(&stmt.condition);
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse, span);
+        self.emit_op(OpCode::Pop, span);
+        self.compile_stmt(&stmt.body);
+        self.emit_loop(loop_start, span);
+        self.patch_jump(exit_jump, span);
+        self.emit_op(OpCode::Pop, span);
+    }
+
+    // Mirrors the scope the interpreter creates around the whole loop, so every
+    // iteration shares the variable declared in the initializer
+    fn visit_for_stmt(&mut self, stmt: &ForStmt) {
+        self.begin_scope();
+        if let Some(initializer) = &stmt.initializer {
+            self.compile_stmt(initializer);
+        }
+        let loop_start = self.chunk().code.len();
+        let mut exit_jump = None;
+        if let Some(condition) = &stmt.condition {
+            let span = condition.span();
+            self.compile_expr(condition);
+            exit_jump = Some((self.emit_jump(OpCode::JumpIfFalse, span), span));
+            self.emit_op(OpCode::Pop, span);
+        }
+        self.compile_stmt(&stmt.body);
+        if let Some(increment) = &stmt.increment {
+            self.compile_expr(increment);
+            self.emit_op(OpCode::Pop, increment.span());
+        }
+        let span = self.previous;
+        self.emit_loop(loop_start, span);
+        if let Some((exit_jump, span)) = exit_jump {
+            self.patch_jump(exit_jump, span);
+            self.emit_op(OpCode::Pop, span);
+        }
+        self.end_scope();
+    }
+
+    // A local function is declared before its body is compiled so it can recurse
+    fn visit_function_stmt(&mut self, stmt: &Rc<FunctionStmt>) {
+        let span = stmt.name.span;
+        if self.current().scope_depth > 0 {
+            self.add_local(&stmt.name.lexeme, span);
+        }
+        self.function(stmt, FunctionKind::Function);
+        if self.current().scope_depth == 0 {
+            let constant = self.string_constant(&stmt.name.lexeme, span);
+            self.emit_with_u16(OpCode::DefineGlobal, constant, span);
+        }
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) {
+        let span = stmt.keyword.span;
+        match &stmt.value {
+            Some(value) => {
+                self.compile_expr(value);
+                self.emit_op(OpCode::Return, span);
+            }
+            None => self.emit_return(span),
+        }
+    }
+
+    // The class is created and named first so its methods can refer to it. A
+    // subclass's methods capture `super` from a scope around them, like they
+    // do in the interpreter.
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) {
+        let name = &stmt.name.lexeme;
+        let span = stmt.name.span;
+        let constant = self.string_constant(name, span);
+        if self.current().scope_depth > 0 {
+            self.add_local(name, span);
+        }
+        self.emit_with_u16(OpCode::Class, constant, span);
+        if self.current().scope_depth == 0 {
+            self.emit_with_u16(OpCode::DefineGlobal, constant, span);
+        }
+
+        if let Some(superclass) = &stmt.superclass {
+            self.visit_variable(superclass);
+            self.begin_scope();
+            self.add_local("super", superclass.name.span);
+            self.get_variable(name, span);
+            self.emit_op(OpCode::Inherit, superclass.name.span);
+        }
+
+        self.get_variable(name, span);
+        for method in &stmt.methods {
+            let kind = if &*method.name.lexeme == "init" {
+                FunctionKind::Initializer
+            } else {
+                FunctionKind::Method
+            };
+            self.function(method, kind);
+            let constant = self.string_constant(&method.name.lexeme, method.name.span);
+            self.emit_with_u16(OpCode::Method, constant, method.name.span);
+        }
+        self.emit_op(OpCode::Pop, span);
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            functions: vec![FunctionState::new(None, FunctionKind::Script)],
+            errors: Vec::new(),
+            previous: Span::default(),
+        }
+    }
+
+    // Compiles a whole program into a script that returns nil
+    pub fn compile_program(mut self, statements: &[Stmt]) -> Result<Rc<Function>, Vec<GloxError>> {
+        self.compile(statements);
+        self.emit_return(self.previous);
+        self.finish()
+    }
+
+    // Compiles an expression into a script that returns its value
+    pub fn compile_expression(mut self, expr: &Expr) -> Result<Rc<Function>, Vec<GloxError>> {
+        self.compile_expr(expr);
+        self.emit_op(OpCode::Return, expr.span());
+        self.finish()
+    }
+
+    fn finish(mut self) -> Result<Rc<Function>, Vec<GloxError>> {
+        let script = self.functions.pop().unwrap().function;
+        if self.errors.is_empty() {
+            Ok(Rc::new(script))
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn compile(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.compile_stmt(statement);
+        }
+    }
+
+    fn compile_stmt(&mut self, stmt: &Stmt) {
+        stmt.accept(self)
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn compile_exprs(&mut self, exprs: &[Expr]) {
+        for expr in exprs {
+            self.compile_expr(expr);
+        }
+    }
+
+    // Compiles the body into a function of its own, then emits the closure that
+    // creates it at runtime along with where each of its upvalues comes from
+    fn function(&mut self, stmt: &FunctionStmt, kind: FunctionKind) {
+        let span = stmt.name.span;
+        let mut state = FunctionState::new(Some(Rc::clone(&stmt.name.lexeme)), kind);
+        state.function.arity = stmt.params.len();
+        self.functions.push(state);
+        self.previous = span;
+        self.begin_scope();
+        for param in &stmt.params {
+            self.add_local(&param.lexeme, param.span);
+        }
+        self.compile(&stmt.body);
+        self.emit_return(self.previous);
+
+        let state = self.functions.pop().unwrap();
+        let mut function = state.function;
+        function.upvalue_count = state.upvalues.len();
+        let index = self.chunk().functions.len();
+        let index = self.count(index, "Too many functions in one chunk.", span);
+        self.chunk().functions.push(Rc::new(function));
+        self.emit_with_u16(OpCode::Closure, index, span);
+        for upvalue in state.upvalues {
+            let chunk = self.chunk();
+            chunk.code.push(upvalue.is_local as u8);
+            chunk.code.push(upvalue.index);
+        }
+    }
+
+    fn current(&mut self) -> &mut FunctionState {
+        self.functions.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.current().function.chunk
+    }
+
+    fn emit_op(&mut self, op: OpCode, span: Span) {
+        self.previous = span;
+        self.chunk().write_op(op, span);
+    }
+
+    fn emit_with_byte(&mut self, op: OpCode, byte: u8, span: Span) {
+        self.emit_op(op, span);
+        self.chunk().code.push(byte);
+    }
+
+    fn emit_with_u16(&mut self, op: OpCode, operand: u16, span: Span) {
+        self.emit_op(op, span);
+        self.chunk().write_u16(operand);
+    }
+
+    // An initializer always returns `this`, even from a bare `return;`
+    fn emit_return(&mut self, span: Span) {
+        if self.current().kind == FunctionKind::Initializer {
+            self.emit_with_byte(OpCode::GetLocal, 0, span);
+        } else {
+            self.emit_op(OpCode::Nil, span);
+        }
+        self.emit_op(OpCode::Return, span);
+    }
+
+    // Emits a jump with a placeholder offset and returns where the offset is, for
+    // patch_jump to fill in once the target is known
+    fn emit_jump(&mut self, op: OpCode, span: Span) -> usize {
+        self.emit_with_u16(op, u16::MAX, span);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize, span: Span) {
+        let jump = self.chunk().code.len() - offset - 2;
+        let Ok(jump) = u16::try_from(jump) else {
+            self.error("Too much code to jump over.", span);
+            return;
+        };
+        let bytes = jump.to_be_bytes();
+        self.chunk().code[offset..offset + 2].copy_from_slice(&bytes);
+    }
+
+    fn emit_loop(&mut self, loop_start: usize, span: Span) {
+        self.emit_op(OpCode::Loop, span);
+        // The offset counts from after its own two bytes
+        let jump = self.chunk().code.len() - loop_start + 2;
+        let jump = u16::try_from(jump).unwrap_or_else(|_| {
+            self.error("Loop body too large.", span);
+            0
+        });
+        self.chunk().write_u16(jump);
+    }
+
+    fn make_constant(&mut self, value: Value, span: Span) -> u16 {
+        let index = self.chunk().constants.len();
+        let index = self.count(index, "Too many constants in one chunk.", span);
+        self.chunk().constants.push(value);
+        index
+    }
+
+    fn string_constant(&mut self, s: &str, span: Span) -> u16 {
+        if let Some(&constant) = self.current().strings.get(s) {
+            return constant;
+        }
+        let s: Rc<str> = Rc::from(s);
+        let constant = self.make_constant(Value::Str(Rc::clone(&s)), span);
+        self.current().strings.insert(s, constant);
+        constant
+    }
+
+    // Checks a count or index fits in a u16 operand
+    fn count(&mut self, count: usize, message: &str, span: Span) -> u16 {
+        u16::try_from(count).unwrap_or_else(|_| {
+            self.error(message, span);
+            0
+        })
+    }
+
+    fn begin_scope(&mut self) {
+        self.current().scope_depth += 1;
+    }
+
+    // Pops the scope's locals off the stack, moving any that were captured
+    // into their upvalues
+    fn end_scope(&mut self) {
+        let span = self.previous;
+        let state = self.current();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+        while let Some(local) = self.current().locals.last() {
+            if local.depth <= depth {
+                break;
+            }
+            let op = if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            self.emit_op(op, span);
+            self.current().locals.pop();
+        }
+    }
+
+    fn add_local(&mut self, name: &str, span: Span) {
+        if self.current().locals.len() == LOCALS_MAX {
+            self.error("Too many local variables in function.", span);
+            return;
+        }
+        let state = self.current();
+        state.locals.push(Local {
+            name: Rc::from(name),
+            depth: state.scope_depth,
+            is_captured: false,
+        });
+    }
+
+    // Takes the value on top of the stack as the variable's, a global is moved
+    // into the globals table and a local just stays where it is
+    fn define_variable(&mut self, name: &str, span: Span) {
+        if self.current().scope_depth > 0 {
+            self.add_local(name, span);
+        } else {
+            let constant = self.string_constant(name, span);
+            self.emit_with_u16(OpCode::DefineGlobal, constant, span);
+        }
+    }
+
+    fn get_variable(&mut self, name: &str, span: Span) {
+        let last = self.functions.len() - 1;
+        if let Some(slot) = self.resolve_local(last, name) {
+            self.emit_with_byte(OpCode::GetLocal, slot, span);
+        } else if let Some(index) = self.resolve_upvalue(last, name, span) {
+            self.emit_with_byte(OpCode::GetUpvalue, index, span);
+        } else {
+            let constant = self.string_constant(name, span);
+            self.emit_with_u16(OpCode::GetGlobal, constant, span);
+        }
+    }
+
+    // The slot of the innermost local with the name in the function
+    fn resolve_local(&self, function: usize, name: &str) -> Option<u8> {
+        let locals = &self.functions[function].locals;
+        locals
+            .iter()
+            .rposition(|local| &*local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    // Looks for the variable in the functions around this one, threading an
+    // upvalue through each function in between. None means it's a global.
+    fn resolve_upvalue(&mut self, function: usize, name: &str, span: Span) -> Option<u8> {
+        if function == 0 {
+            return None;
+        }
+        if let Some(slot) = self.resolve_local(function - 1, name) {
+            self.functions[function - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(function, slot, true, span));
+        }
+        let index = self.resolve_upvalue(function - 1, name, span)?;
+        Some(self.add_upvalue(function, index, false, span))
+    }
+
+    fn add_upvalue(&mut self, function: usize, index: u8, is_local: bool, span: Span) -> u8 {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &self.functions[function].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+        if upvalues.len() == LOCALS_MAX {
+            self.error("Too many closure variables in function.", span);
+            return 0;
+        }
+        self.functions[function].upvalues.push(upvalue);
+        (self.functions[function].upvalues.len() - 1) as u8
+    }
+
+    // Once a limit is hit most of what comes after goes over it too, so each
+    // limit is only reported the first time
+    fn error(&mut self, message: &str, span: Span) {
+        let repeated = self
+            .errors
+            .iter()
+            .map(GloxError::diagnostic)
+            .any(|e| e.message == message);
+        if !repeated {
+            self.errors.push(GloxError::compile(message, span));
+        }
+    }
+}
@@ -1,21 +1,205 @@
 use std::fmt;
 
+use crate::token::Span;
+
+// The details shared by every error that points at a place in the source
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    // An optional hint on how to fix the problem, rendered below the source excerpt
+    pub help: Option<String>,
+}
+
 #[derive(Debug)]
 pub enum GloxError {
-    // Used during Parsing, holds the bad token and the line number
-    UnexpectedToken(String, i32),
+    // Used during Scanning, for characters that can't start any token
+    UnexpectedCharacter(Diagnostic),
+
+    // Used during Scanning, points at the opening quote
+    UnterminatedString(Diagnostic),
+
+    // Used during Scanning, for number literals that can't be represented
+    InvalidNumber(Diagnostic),
+
+    // Used during Scanning, for backslashes in strings that don't escape anything
+    InvalidEscape(Diagnostic),
+
+    // Used during Parsing, points at the bad token
+    UnexpectedToken(Diagnostic),
+
+    // Used by the resolver between parsing and interpreting
+    ResolutionError(Diagnostic),
+
+    // Used by the bytecode compiler, for programs that go past its limits
+    CompileError(Diagnostic),
 
     // Used during Interpreter pass
-    RuntimeError(String)
+    RuntimeError(Diagnostic),
 }
 
-impl fmt::Display for GloxError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl GloxError {
+    pub fn unexpected_character(c: char, span: Span) -> GloxError {
+        let message = format!("Unexpected character '{}'.", c);
+        GloxError::UnexpectedCharacter(Diagnostic::new(message, span))
+    }
+
+    pub fn unterminated_string(span: Span) -> GloxError {
+        GloxError::UnterminatedString(Diagnostic::new("Unterminated string.", span))
+    }
+
+    pub fn invalid_number(message: impl Into<String>, span: Span) -> GloxError {
+        GloxError::InvalidNumber(Diagnostic::new(message, span))
+    }
+
+    pub fn invalid_escape(message: impl Into<String>, span: Span) -> GloxError {
+        GloxError::InvalidEscape(Diagnostic::new(message, span))
+    }
+
+    pub fn parse(message: impl Into<String>, span: Span) -> GloxError {
+        GloxError::UnexpectedToken(Diagnostic::new(message, span))
+    }
+
+    pub fn resolution(message: impl Into<String>, span: Span) -> GloxError {
+        GloxError::ResolutionError(Diagnostic::new(message, span))
+    }
+
+    pub fn compile(message: impl Into<String>, span: Span) -> GloxError {
+        GloxError::CompileError(Diagnostic::new(message, span))
+    }
+
+    pub fn runtime(message: impl Into<String>, span: Span) -> GloxError {
+        GloxError::RuntimeError(Diagnostic::new(message, span))
+    }
+
+    // For errors raised inside native functions, which don't know where they
+    // were called from. The interpreter points them at the call.
+    pub fn native(message: impl Into<String>) -> GloxError {
+        GloxError::runtime(message, Span::default())
+    }
+
+    // Points the error at span, unless it already points somewhere
+    pub fn or_span(mut self, span: Span) -> GloxError {
+        let diagnostic = self.diagnostic_mut();
+        if diagnostic.span == Span::default() {
+            diagnostic.span = span;
+        }
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> GloxError {
+        self.diagnostic_mut().help = Some(help.into());
+        self
+    }
+
+    pub fn diagnostic(&self) -> &Diagnostic {
         match self {
-            GloxError::UnexpectedToken(s, line) => write!(f, "Parsing error, unexpected token: {} at line: {}", s, line),
-            GloxError::RuntimeError(s) => write!(f, "RuntimeError: {}", s)
+            GloxError::UnexpectedCharacter(d)
+            | GloxError::UnterminatedString(d)
+            | GloxError::InvalidNumber(d)
+            | GloxError::InvalidEscape(d)
+            | GloxError::UnexpectedToken(d)
+            | GloxError::ResolutionError(d)
+            | GloxError::CompileError(d)
+            | GloxError::RuntimeError(d) => d,
         }
     }
+
+    fn diagnostic_mut(&mut self) -> &mut Diagnostic {
+        match self {
+            GloxError::UnexpectedCharacter(d)
+            | GloxError::UnterminatedString(d)
+            | GloxError::InvalidNumber(d)
+            | GloxError::InvalidEscape(d)
+            | GloxError::UnexpectedToken(d)
+            | GloxError::ResolutionError(d)
+            | GloxError::CompileError(d)
+            | GloxError::RuntimeError(d) => d,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            GloxError::UnexpectedCharacter(_)
+            | GloxError::UnterminatedString(_)
+            | GloxError::InvalidNumber(_)
+            | GloxError::InvalidEscape(_) => "Lexical error",
+            GloxError::UnexpectedToken(_) => "Parsing error",
+            GloxError::ResolutionError(_) => "Resolution error",
+            GloxError::CompileError(_) => "Compile error",
+            GloxError::RuntimeError(_) => "RuntimeError",
+        }
+    }
+
+    // Renders the error along with the offending line of source, e.g.
+    //
+    // RuntimeError: Tried to subtract something other than two numbers
+    //   --> script.lox:3:7
+    //   |
+    // 3 | print 1 - "a";
+    //   |       ^^^^^^^
+    //   = help: ...
+    pub fn render(&self, source: &str, filename: &str) -> String {
+        let diagnostic = self.diagnostic();
+        let span = diagnostic.span;
+        let mut out = format!("{}: {}\n", self.title(), diagnostic.message);
+
+        let line_number = span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        out.push_str(&format!(
+            "{} --> {}:{}:{}\n",
+            gutter, filename, span.line, span.column
+        ));
+
+        if let Some(line) = source.lines().nth(span.line.saturating_sub(1)) {
+            // Only the part of the span on its first line gets underlined
+            let line = line.trim_end_matches('\r').trim_start_matches('\u{feff}');
+            let line_length = line.chars().count();
+            let start = span.column.saturating_sub(1).min(line_length);
+            let spanned = source
+                .get(span.start..span.end)
+                .and_then(|s| s.lines().next())
+                .map_or(0, |s| s.chars().count());
+            let width = spanned.min(line_length - start).max(1);
+            out.push_str(&format!("{} |\n", gutter));
+            out.push_str(&format!("{} | {}\n", line_number, line));
+            out.push_str(&format!(
+                "{} | {}{}\n",
+                gutter,
+                " ".repeat(start),
+                "^".repeat(width)
+            ));
+        }
+
+        if let Some(help) = &diagnostic.help {
+            out.push_str(&format!("{} = help: {}\n", gutter, help));
+        }
+        out
+    }
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Diagnostic {
+        Diagnostic {
+            message: message.into(),
+            span,
+            help: None,
+        }
+    }
+}
+
+impl fmt::Display for GloxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = self.diagnostic();
+        write!(
+            f,
+            "{}: {} at line: {}:{}",
+            self.title(),
+            d.message,
+            d.span.line,
+            d.span.column
+        )
+    }
 }
 
 impl std::error::Error for GloxError {}
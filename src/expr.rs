@@ -1,6 +1,27 @@
-use crate::error::GloxError;
-use crate::interpreter::Visitor;
-use crate::token::{Literal, Token};
+use std::cell::Cell;
+
+use crate::token::{AstToken, Literal, Span};
+
+// Implemented by every pass over expressions, T is what each visit produces
+pub trait Visitor<T> {
+    fn visit_literal(&mut self, expr: &LiteralExpr) -> T;
+    fn visit_grouping(&mut self, expr: &Grouping) -> T;
+    fn visit_unary(&mut self, expr: &Unary) -> T;
+    fn visit_binary(&mut self, expr: &Binary) -> T;
+    fn visit_variable(&mut self, expr: &Variable) -> T;
+    fn visit_assign(&mut self, expr: &Assign) -> T;
+    fn visit_logical(&mut self, expr: &Logical) -> T;
+    fn visit_call(&mut self, expr: &Call) -> T;
+    fn visit_get(&mut self, expr: &Get) -> T;
+    fn visit_set(&mut self, expr: &Set) -> T;
+    fn visit_this(&mut self, expr: &This) -> T;
+    fn visit_super(&mut self, expr: &Super) -> T;
+    fn visit_list(&mut self, expr: &ListExpr) -> T;
+    fn visit_map(&mut self, expr: &MapExpr) -> T;
+    fn visit_index(&mut self, expr: &Index) -> T;
+    fn visit_set_index(&mut self, expr: &SetIndex) -> T;
+    fn visit_interpolate(&mut self, expr: &Interpolate) -> T;
+}
 
 #[derive(Clone, Debug)]
 pub enum Expr {
@@ -8,15 +29,64 @@ pub enum Expr {
     Binary(Binary),
     Unary(Unary),
     Literal(LiteralExpr),
+    Variable(Variable),
+    Assign(Assign),
+    Logical(Logical),
+    Call(Call),
+    Get(Get),
+    Set(Set),
+    This(This),
+    Super(Super),
+    List(ListExpr),
+    Map(MapExpr),
+    Index(Index),
+    SetIndex(SetIndex),
+    Interpolate(Interpolate),
 }
 
 impl Expr {
-    pub fn accept(&self, visitor: impl Visitor) -> Result<Literal, GloxError> {
+    pub fn accept<T>(&self, visitor: &mut impl Visitor<T>) -> T {
         match self {
             Expr::Grouping(x) => visitor.visit_grouping(x),
             Expr::Unary(x) => visitor.visit_unary(x),
             Expr::Binary(x) => visitor.visit_binary(x),
             Expr::Literal(x) => visitor.visit_literal(x),
+            Expr::Variable(x) => visitor.visit_variable(x),
+            Expr::Assign(x) => visitor.visit_assign(x),
+            Expr::Logical(x) => visitor.visit_logical(x),
+            Expr::Call(x) => visitor.visit_call(x),
+            Expr::Get(x) => visitor.visit_get(x),
+            Expr::Set(x) => visitor.visit_set(x),
+            Expr::This(x) => visitor.visit_this(x),
+            Expr::Super(x) => visitor.visit_super(x),
+            Expr::List(x) => visitor.visit_list(x),
+            Expr::Map(x) => visitor.visit_map(x),
+            Expr::Index(x) => visitor.visit_index(x),
+            Expr::SetIndex(x) => visitor.visit_set_index(x),
+            Expr::Interpolate(x) => visitor.visit_interpolate(x),
+        }
+    }
+
+    // The region of source the expression was parsed from, used to point at it in errors
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Grouping(x) => x.span,
+            Expr::Unary(x) => x.operator.span.to(x.right.span()),
+            Expr::Binary(x) => x.left.span().to(x.right.span()),
+            Expr::Literal(x) => x.span,
+            Expr::Variable(x) => x.name.span,
+            Expr::Assign(x) => x.name.span.to(x.value.span()),
+            Expr::Logical(x) => x.left.span().to(x.right.span()),
+            Expr::Call(x) => x.callee.span().to(x.paren.span),
+            Expr::Get(x) => x.object.span().to(x.name.span),
+            Expr::Set(x) => x.object.span().to(x.value.span()),
+            Expr::This(x) => x.keyword.span,
+            Expr::Super(x) => x.keyword.span.to(x.method.span),
+            Expr::List(x) => x.span,
+            Expr::Map(x) => x.span,
+            Expr::Index(x) => x.object.span().to(x.bracket.span),
+            Expr::SetIndex(x) => x.object.span().to(x.value.span()),
+            Expr::Interpolate(x) => x.span,
         }
     }
 }
@@ -24,18 +94,20 @@ impl Expr {
 #[derive(Clone, Debug)]
 pub struct Grouping {
     pub expression: Box<Expr>,
+    // Includes the parentheses
+    pub span: Span,
 }
 
 #[derive(Clone, Debug)]
 pub struct Binary {
     pub left: Box<Expr>,
-    pub operator: Token,
+    pub operator: AstToken,
     pub right: Box<Expr>,
 }
 
 #[derive(Clone, Debug)]
 pub struct Unary {
-    pub operator: Token,
+    pub operator: AstToken,
     pub right: Box<Expr>,
 }
 
@@ -44,4 +116,108 @@ pub struct Unary {
 #[derive(Clone, Debug)]
 pub struct LiteralExpr {
     pub value: Literal,
+    pub span: Span,
+}
+
+// How many scopes out from the reference the variable was declared. It's filled
+// in by the resolver after parsing, None means the variable is a global.
+pub type Depth = Cell<Option<usize>>;
+
+#[derive(Clone, Debug)]
+pub struct Variable {
+    pub name: AstToken,
+    pub depth: Depth,
+}
+
+#[derive(Clone, Debug)]
+pub struct Assign {
+    pub name: AstToken,
+    pub value: Box<Expr>,
+    pub depth: Depth,
+}
+
+// Kept separate from Binary because `and` and `or` short-circuit
+#[derive(Clone, Debug)]
+pub struct Logical {
+    pub left: Box<Expr>,
+    pub operator: AstToken,
+    pub right: Box<Expr>,
+}
+
+// The closing paren is kept so runtime errors in the call can report its line
+#[derive(Clone, Debug)]
+pub struct Call {
+    pub callee: Box<Expr>,
+    pub paren: AstToken,
+    pub arguments: Vec<Expr>,
+}
+
+// Property access, `object.name`
+#[derive(Clone, Debug)]
+pub struct Get {
+    pub object: Box<Expr>,
+    pub name: AstToken,
+}
+
+// Property assignment, `object.name = value`
+#[derive(Clone, Debug)]
+pub struct Set {
+    pub object: Box<Expr>,
+    pub name: AstToken,
+    pub value: Box<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct This {
+    pub keyword: AstToken,
+    pub depth: Depth,
+}
+
+// `super.method`, a super expression is always followed by a method access
+#[derive(Clone, Debug)]
+pub struct Super {
+    pub keyword: AstToken,
+    pub method: AstToken,
+    pub depth: Depth,
+}
+
+// A list literal, `[a, b, c]`. The span includes the brackets.
+#[derive(Clone, Debug)]
+pub struct ListExpr {
+    pub elements: Vec<Expr>,
+    pub span: Span,
+}
+
+// A map literal, `{key: value, ...}`. Only parsed where an expression is expected,
+// a `{` that starts a statement is always a block.
+#[derive(Clone, Debug)]
+pub struct MapExpr {
+    pub entries: Vec<(Expr, Expr)>,
+    pub span: Span,
+}
+
+// `object[index]`, the closing bracket is kept for the span like Call's paren
+#[derive(Clone, Debug)]
+pub struct Index {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub bracket: AstToken,
+}
+
+// `object[index] = value`
+#[derive(Clone, Debug)]
+pub struct SetIndex {
+    pub object: Box<Expr>,
+    pub index: Box<Expr>,
+    pub bracket: AstToken,
+    pub value: Box<Expr>,
+}
+
+// An interpolated string, `"a ${b} c"`. The parts are the string literals and
+// the interpolated expressions in order, each is converted to a string the way
+// print would and they're joined. The span covers the whole string.
+#[derive(Clone, Debug)]
+pub struct Interpolate {
+    pub parts: Vec<Expr>,
+    pub span: Span,
 }
@@ -0,0 +1,267 @@
+use std::rc::Rc;
+
+use crate::expr::{
+    Assign, Binary, Call, Get, Grouping, Index, Interpolate, ListExpr, LiteralExpr, Logical,
+    MapExpr, Set, SetIndex, Super, This, Unary, Variable, Visitor,
+};
+use crate::stmt::{
+    BlockStmt, ClassStmt, ExpressionStmt, ForStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt,
+    Stmt, StmtVisitor, VarStmt, WhileStmt,
+};
+
+// Prints a program back out as Lox source in one consistent style: two space
+// indents, one statement per line, braces on the same line as their statement
+// and a blank line around functions and classes. Literals are copied from the
+// source as written. Comments never make it past the scanner, so Glox::format
+// won't format a script that has any rather than lose them.
+pub struct Formatter<'a> {
+    source: &'a str,
+    indent: usize,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(source: &'a str) -> Formatter<'a> {
+        Formatter { source, indent: 0 }
+    }
+
+    pub fn format(&mut self, statements: &[Stmt]) -> String {
+        let mut out = String::new();
+        for (i, stmt) in statements.iter().enumerate() {
+            if i > 0 && (is_declaration(stmt) || is_declaration(&statements[i - 1])) {
+                out.push('\n');
+            }
+            out.push_str(&self.pad());
+            out.push_str(&stmt.accept(self));
+            out.push('\n');
+        }
+        out
+    }
+
+    fn pad(&self) -> String {
+        "  ".repeat(self.indent)
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> String {
+        if statements.is_empty() {
+            return "{}".to_string();
+        }
+        self.indent += 1;
+        let body = self.format(statements);
+        self.indent -= 1;
+        format!("{{\n{}{}}}", body, self.pad())
+    }
+
+    // The body of an if or a loop. A block opens on the same line, anything
+    // else goes on its own line indented one level deeper.
+    fn body(&mut self, stmt: &Stmt) -> String {
+        if let Stmt::Block(block) = stmt {
+            return format!(" {}", self.block(&block.statements));
+        }
+        self.indent += 1;
+        let body = format!("\n{}{}", self.pad(), stmt.accept(self));
+        self.indent -= 1;
+        body
+    }
+
+    fn function(&mut self, function: &FunctionStmt) -> String {
+        let params: Vec<&str> = function.params.iter().map(|p| &*p.lexeme).collect();
+        format!(
+            "{}({}) {}",
+            function.name.lexeme,
+            params.join(", "),
+            self.block(&function.body)
+        )
+    }
+}
+
+fn is_declaration(stmt: &Stmt) -> bool {
+    matches!(stmt, Stmt::Function(_) | Stmt::Class(_))
+}
+
+impl Visitor<String> for Formatter<'_> {
+    fn visit_literal(&mut self, expr: &LiteralExpr) -> String {
+        match self.source.get(expr.span.start..expr.span.end) {
+            Some(text) if !text.is_empty() => text.to_string(),
+            _ => expr.value.to_string(),
+        }
+    }
+
+    fn visit_grouping(&mut self, expr: &Grouping) -> String {
+        format!("({})", expr.expression.accept(self))
+    }
+
+    fn visit_unary(&mut self, expr: &Unary) -> String {
+        format!("{}{}", expr.operator.lexeme, expr.right.accept(self))
+    }
+
+    fn visit_binary(&mut self, expr: &Binary) -> String {
+        let left = expr.left.accept(self);
+        let right = expr.right.accept(self);
+        format!("{} {} {}", left, expr.operator.lexeme, right)
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) -> String {
+        expr.name.lexeme.to_string()
+    }
+
+    fn visit_assign(&mut self, expr: &Assign) -> String {
+        format!("{} = {}", expr.name.lexeme, expr.value.accept(self))
+    }
+
+    fn visit_logical(&mut self, expr: &Logical) -> String {
+        let left = expr.left.accept(self);
+        let right = expr.right.accept(self);
+        format!("{} {} {}", left, expr.operator.lexeme, right)
+    }
+
+    fn visit_call(&mut self, expr: &Call) -> String {
+        let callee = expr.callee.accept(self);
+        let arguments: Vec<String> = expr.arguments.iter().map(|a| a.accept(self)).collect();
+        format!("{}({})", callee, arguments.join(", "))
+    }
+
+    fn visit_get(&mut self, expr: &Get) -> String {
+        format!("{}.{}", expr.object.accept(self), expr.name.lexeme)
+    }
+
+    fn visit_set(&mut self, expr: &Set) -> String {
+        let object = expr.object.accept(self);
+        let value = expr.value.accept(self);
+        format!("{}.{} = {}", object, expr.name.lexeme, value)
+    }
+
+    fn visit_this(&mut self, _expr: &This) -> String {
+        "this".to_string()
+    }
+
+    fn visit_super(&mut self, expr: &Super) -> String {
+        format!("super.{}", expr.method.lexeme)
+    }
+
+    fn visit_list(&mut self, expr: &ListExpr) -> String {
+        let elements: Vec<String> = expr.elements.iter().map(|e| e.accept(self)).collect();
+        format!("[{}]", elements.join(", "))
+    }
+
+    fn visit_map(&mut self, expr: &MapExpr) -> String {
+        let entries: Vec<String> = expr
+            .entries
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key.accept(self), value.accept(self)))
+            .collect();
+        format!("{{{}}}", entries.join(", "))
+    }
+
+    fn visit_index(&mut self, expr: &Index) -> String {
+        format!("{}[{}]", expr.object.accept(self), expr.index.accept(self))
+    }
+
+    fn visit_set_index(&mut self, expr: &SetIndex) -> String {
+        let object = expr.object.accept(self);
+        let index = expr.index.accept(self);
+        let value = expr.value.accept(self);
+        format!("{}[{}] = {}", object, index, value)
+    }
+
+    // Printed as written, the parts can't be told apart from the escapes
+    // around them once the string has been scanned
+    fn visit_interpolate(&mut self, expr: &Interpolate) -> String {
+        self.source[expr.span.start..expr.span.end].to_string()
+    }
+}
+
+impl StmtVisitor<String> for Formatter<'_> {
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) -> String {
+        format!("{};", stmt.expression.accept(self))
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> String {
+        format!("print {};", stmt.expression.accept(self))
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> String {
+        match &stmt.initializer {
+            Some(initializer) => {
+                format!("var {} = {};", stmt.name.lexeme, initializer.accept(self))
+            }
+            None => format!("var {};", stmt.name.lexeme),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> String {
+        self.block(&stmt.statements)
+    }
+
+    // An else after a block shares the closing brace's line, and an else if
+    // chain is kept flat rather than nesting deeper with every branch
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> String {
+        let condition = stmt.condition.accept(self);
+        let mut out = format!("if ({}){}", condition, self.body(&stmt.then_branch));
+        if let Some(else_branch) = &stmt.else_branch {
+            if matches!(*stmt.then_branch, Stmt::Block(_)) {
+                out.push_str(" else");
+            } else {
+                out.push_str(&format!("\n{}else", self.pad()));
+            }
+            match &**else_branch {
+                Stmt::If(_) => out.push_str(&format!(" {}", else_branch.accept(self))),
+                _ => out.push_str(&self.body(else_branch)),
+            }
+        }
+        out
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> String {
+        let condition = stmt.condition.accept(self);
+        format!("while ({}){}", condition, self.body(&stmt.body))
+    }
+
+    fn visit_for_stmt(&mut self, stmt: &ForStmt) -> String {
+        // The initializer is a statement so it brings its own semicolon
+        let mut clauses = match &stmt.initializer {
+            Some(initializer) => initializer.accept(self),
+            None => ";".to_string(),
+        };
+        if let Some(condition) = &stmt.condition {
+            clauses.push_str(&format!(" {}", condition.accept(self)));
+        }
+        clauses.push(';');
+        if let Some(increment) = &stmt.increment {
+            clauses.push_str(&format!(" {}", increment.accept(self)));
+        }
+        format!("for ({}){}", clauses, self.body(&stmt.body))
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<FunctionStmt>) -> String {
+        format!("fun {}", self.function(stmt))
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> String {
+        match &stmt.value {
+            Some(value) => format!("return {};", value.accept(self)),
+            None => "return;".to_string(),
+        }
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> String {
+        let mut out = format!("class {}", stmt.name.lexeme);
+        if let Some(superclass) = &stmt.superclass {
+            out.push_str(&format!(" < {}", superclass.name.lexeme));
+        }
+        if stmt.methods.is_empty() {
+            out.push_str(" {}");
+            return out;
+        }
+        out.push_str(" {\n");
+        self.indent += 1;
+        for (i, method) in stmt.methods.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(&format!("{}{}\n", self.pad(), self.function(method)));
+        }
+        self.indent -= 1;
+        out.push_str(&format!("{}}}", self.pad()));
+        out
+    }
+}
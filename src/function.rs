@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::class::LoxInstance;
+use crate::error::GloxError;
+use crate::interpreter::{Environment, Interpreter, Unwind};
+use crate::object::Value;
+use crate::stmt::FunctionStmt;
+use crate::token::Span;
+
+// How deep calls to Lox functions can nest before it's a runtime error. The top
+// level of the script counts as one.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+pub fn stack_overflow(span: Span) -> GloxError {
+    GloxError::runtime("Stack overflow.", span).with_help("check for recursion that never stops")
+}
+
+// Anything that can be invoked with a call expression
+pub trait LoxCallable {
+    fn arity(&self) -> usize;
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, GloxError>;
+}
+
+// A user defined function along with the environment it was declared in,
+// holding on to that environment is what makes closures work
+pub struct LoxFunction {
+    declaration: Rc<FunctionStmt>,
+    closure: Rc<RefCell<Environment>>,
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        declaration: Rc<FunctionStmt>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> LoxFunction {
+        LoxFunction {
+            declaration,
+            closure,
+            is_initializer,
+        }
+    }
+
+    // Creates a copy of the method whose closure has `this` bound to the instance
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        environment.define("this", Value::Instance(instance));
+        LoxFunction::new(
+            Rc::clone(&self.declaration),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+
+    pub fn name(&self) -> &str {
+        &self.declaration.name.lexeme
+    }
+}
+
+impl LoxCallable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, GloxError> {
+        let mut environment = Environment::new_enclosed(Rc::clone(&self.closure));
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+        let environment = Rc::new(RefCell::new(environment));
+        let result = interpreter.execute_block(&self.declaration.body, environment);
+        // An initializer always returns the instance, even from an early `return;`
+        if self.is_initializer {
+            return match result {
+                Ok(()) | Err(Unwind::Return(_)) => Ok(self
+                    .closure
+                    .borrow()
+                    .lookup_at(0, "this")
+                    .unwrap_or(Value::Nil)),
+                Err(Unwind::Error(e)) => Err(e),
+            };
+        }
+        match result {
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(e)) => Err(e),
+        }
+    }
+}
+
+// The closure can contain the function itself, so we can't derive Debug
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
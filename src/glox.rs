@@ -1,74 +1,204 @@
-use std::{fs, io, process};
+use std::rc::Rc;
 
+use crate::ast_printer::AstPrinter;
+use crate::chunk::Function;
+use crate::compiler::Compiler;
+use crate::error::GloxError;
+use crate::formatter::Formatter;
 use crate::interpreter::Interpreter;
+use crate::native::IntoNative;
+use crate::object::{NativeFunction, Value};
 use crate::parser::Parser;
+use crate::resolver::Resolver;
 use crate::scanner::Scanner;
+use crate::stmt::Stmt;
 use crate::token::Token;
+use crate::vm::Vm;
+
+// An instance of the language, used by the glox binary and by Rust programs that
+// embed Lox. Globals live as long as the instance, so a host can define some,
+// run a script against them and read back whatever the script left behind.
+//
+// Errors found before running (lexing, parsing and resolving) are collected and
+// returned together, and nothing is run. A runtime error stops the program and
+// comes back on its own. Use GloxError::render to show them with the source.
 pub struct Glox {
-    had_error: bool,
-    scanner: Scanner,
-    interpreter: Interpreter,
+    engine: Engine,
+}
+
+// How an instance runs code. Both give programs the same output and errors, the
+// tree-walker is kept as the reference the VM can be tested against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    // Compiles to bytecode and runs it on a stack machine
+    #[default]
+    Vm,
+    // Evaluates the syntax tree directly
+    TreeWalker,
+}
+
+enum Engine {
+    Vm(Vm),
+    TreeWalker(Interpreter),
 }
 
 impl Glox {
-    pub fn new_from_file(filepath: &String) -> Self {
-        let code = fs::read_to_string(filepath).expect("failed to read file: {filepath}");
-        Glox {
-            had_error: false,
-            scanner: Scanner::new(code),
-            interpreter: Interpreter::new(),
+    pub fn new() -> Glox {
+        Glox::with_backend(Backend::default())
+    }
+
+    pub fn with_backend(backend: Backend) -> Glox {
+        let engine = match backend {
+            Backend::Vm => Engine::Vm(Vm::new()),
+            Backend::TreeWalker => Engine::TreeWalker(Interpreter::new()),
+        };
+        Glox { engine }
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self.engine {
+            Engine::Vm(_) => Backend::Vm,
+            Engine::TreeWalker(_) => Backend::TreeWalker,
         }
     }
 
-    pub fn new_for_prompt() -> Self {
-        Glox {
-            had_error: false,
-            scanner: Scanner::new(String::new()),
-            interpreter: Interpreter::new(),
+    // Runs the source as a program
+    pub fn run(&mut self, source: &str) -> Result<(), Vec<GloxError>> {
+        let statements = Glox::compile(source)?;
+        match &mut self.engine {
+            Engine::Vm(vm) => {
+                let script = Compiler::new().compile_program(&statements)?;
+                vm.interpret(script).map(|_| ()).map_err(|e| vec![e])
+            }
+            Engine::TreeWalker(interpreter) => {
+                interpreter.interpret(&statements).map_err(|e| vec![e])
+            }
         }
     }
 
-    pub fn run_file(&mut self, filepath: &String) {
-        println!("{}", format!("Running file: {}", filepath));
+    // Evaluates a single expression, such as `total * 2`, and returns its value
+    pub fn eval(&mut self, source: &str) -> Result<Value, Vec<GloxError>> {
+        let expr = Parser::new(source).parse_expression()?;
+        Resolver::new().resolve_expression(&expr)?;
+        match &mut self.engine {
+            Engine::Vm(vm) => {
+                let script = Compiler::new().compile_expression(&expr)?;
+                vm.interpret(script).map_err(|e| vec![e])
+            }
+            Engine::TreeWalker(interpreter) => interpreter.evaluate(&expr).map_err(|e| vec![e]),
+        }
+    }
 
-        self.run();
-        if self.had_error {
-            process::exit(65);
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        match &self.engine {
+            Engine::Vm(vm) => vm.get_global(name),
+            Engine::TreeWalker(interpreter) => interpreter.get_global(name),
         }
     }
 
-    // TODO: Fix this prompt, it's not printing and is not passing the line to the scanner.
-    pub fn run_prompt(&mut self) {
-        loop {
-            print!("$$$");
-            let mut line = String::new();
-            io::stdin()
-                .read_line(&mut line)
-                .expect("failed to read line");
-            if line.is_empty() {
-                break;
-            }
-            self.run();
-            // Reset the error flag to allow users to keep entering commands
-            self.had_error = false;
+    // Defines the global, replacing any existing one with the same name
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        match &mut self.engine {
+            Engine::Vm(vm) => vm.define_global(name, value),
+            Engine::TreeWalker(interpreter) => interpreter.define_global(name, value),
         }
     }
 
-    fn run(&mut self) {
-        let tokens: Vec<Token> = self.scanner.scan_tokens();
-        let mut parser = Parser::new(tokens);
-
-        match parser.parse() {
-            Ok(expr) => match self.interpreter.interpret(&expr) {
-                Ok(result) => println!("{:?}", result),
-                Err(e) => {
-                    println!("Error: {}", e)
-                }
-            },
-            Err(e) => {
-                self.had_error = true;
-                eprintln!("{}", e)
-            }
+    // Every global, sorted by name
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        match &self.engine {
+            Engine::Vm(vm) => vm.globals(),
+            Engine::TreeWalker(interpreter) => interpreter.globals(),
+        }
+    }
+
+    // Makes a Rust function callable from Lox under the given name. Scripts get a
+    // runtime error if they call it with anything other than `arity` arguments.
+    pub fn define_native(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, GloxError> + 'static,
+    ) {
+        let native = NativeFunction::new(name, arity, function);
+        self.set_global(name, Value::Native(Rc::new(native)));
+    }
+
+    // Like define_native, except the arity and the conversion of arguments and
+    // the result come from the closure's signature, e.g.
+    //
+    //     glox.register("greet", |name: String| format!("Hello, {}!", name));
+    //
+    // Arguments of the wrong type, and Err results, are runtime errors in the script.
+    pub fn register<Args>(&mut self, name: &str, function: impl IntoNative<Args>) {
+        let native = function.into_native(name);
+        self.set_global(name, Value::Native(Rc::new(native)));
+    }
+
+    // The rest look at source without running it, for tooling like the glox binary
+
+    pub fn tokenize(source: &str) -> Result<Vec<Token<'_>>, Vec<GloxError>> {
+        Scanner::new(source).scan_tokens()
+    }
+
+    // Like tokenize, but the tokens are scanned one at a time as they're asked
+    // for. Errors come out in among them, and scanning carries on after one.
+    pub fn tokens(source: &str) -> impl Iterator<Item = Result<Token<'_>, GloxError>> + '_ {
+        Scanner::new(source)
+    }
+
+    // Reports every error parsing and resolving the source finds, which is what
+    // would stop it running on either backend. It isn't compiled, so programs too
+    // big for the VM's bytecode pass, see disassemble for those.
+    pub fn check(source: &str) -> Result<(), Vec<GloxError>> {
+        Glox::compile(source).map(|_| ())
+    }
+
+    // The bytecode the VM runs for the source, one listing per function, see
+    // Function::disassemble
+    pub fn disassemble(source: &str) -> Result<String, Vec<GloxError>> {
+        Glox::bytecode(source).map(|script| script.disassemble())
+    }
+
+    // The tree the parser produces, see AstPrinter. A lone expression is shown
+    // by itself rather than as a statement.
+    pub fn syntax_tree(source: &str) -> Result<String, Vec<GloxError>> {
+        let mut printer = AstPrinter::new();
+        if let Ok(expr) = Parser::new(source).parse_expression() {
+            return Ok(printer.print_expr(&expr));
         }
+        let statements = Parser::new(source).parse()?;
+        Ok(printer.print(&statements))
+    }
+
+    // The source reformatted, see Formatter. Source with comments in it is an
+    // error, as the formatter would drop them.
+    pub fn format(source: &str) -> Result<String, Vec<GloxError>> {
+        let statements = Parser::new(source).parse()?;
+        let mut scanner = Scanner::new(source);
+        scanner.by_ref().for_each(drop);
+        if let Some(&comment) = scanner.comments().first() {
+            let error = GloxError::parse("Can't format a script with comments in it.", comment)
+                .with_help("the formatter doesn't keep comments yet, so it would lose them");
+            return Err(vec![error]);
+        }
+        Ok(Formatter::new(source).format(&statements))
+    }
+
+    fn compile(source: &str) -> Result<Vec<Stmt>, Vec<GloxError>> {
+        let statements = Parser::new(source).parse()?;
+        Resolver::new().resolve_program(&statements)?;
+        Ok(statements)
+    }
+
+    fn bytecode(source: &str) -> Result<Rc<Function>, Vec<GloxError>> {
+        let statements = Glox::compile(source)?;
+        Compiler::new().compile_program(&statements)
+    }
+}
+
+impl Default for Glox {
+    fn default() -> Glox {
+        Glox::new()
     }
 }
@@ -1,146 +1,573 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::class::{LoxClass, LoxInstance};
 use crate::error::GloxError;
-use crate::expr::{Binary, Grouping, Unary};
+use crate::expr::{
+    Assign, Binary, Call, Depth, Get, Grouping, Index, Interpolate, ListExpr, Logical, MapExpr,
+    Set, SetIndex, Super, This, Unary, Variable, Visitor,
+};
 use crate::expr::{Expr, LiteralExpr};
-use crate::token::{Literal, TokenType};
+use crate::function::{LoxCallable, LoxFunction, MAX_CALL_DEPTH, stack_overflow};
+use crate::map::LoxMap;
+use crate::object::Value;
+use crate::operators::{binary, get_index, negate, set_index};
+use crate::stdlib;
+use crate::stmt::{
+    BlockStmt, ClassStmt, ExpressionStmt, ForStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt,
+    Stmt, StmtVisitor, VarStmt, WhileStmt,
+};
+use crate::token::{AstToken, Span, TokenType};
 
-pub trait Visitor {
-    fn visit_literal(&self, expr: &LiteralExpr) -> Result<Literal, GloxError>;
-    fn visit_grouping(&self, expr: &Grouping) -> Result<Literal, GloxError>;
-    fn visit_unary(&self, expr: &Unary) -> Result<Literal, GloxError>;
-    fn visit_binary(&self, expr: &Binary) -> Result<Literal, GloxError>;
+// Variables live in a chain of environments, one per scope. Lookups walk outwards
+// from the innermost scope until they reach the globals.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
-#[derive(Copy, Clone)]
-pub struct Interpreter {}
+impl Environment {
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    pub fn new_enclosed(enclosing: Rc<RefCell<Environment>>) -> Environment {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // Redefining an existing variable is allowed and simply overwrites it
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &AstToken) -> Result<Value, GloxError> {
+        if let Some(value) = self.values.get(&*name.lexeme) {
+            return Ok(value.clone());
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    // Looks the name up exactly `distance` environments out, as computed by the
+    // resolver. Takes a plain name so it also works for `this` and `super`.
+    pub fn lookup_at(&self, distance: usize, name: &str) -> Option<Value> {
+        if distance == 0 {
+            return self.values.get(name).cloned();
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().lookup_at(distance - 1, name),
+            None => None,
+        }
+    }
+
+    pub fn get_at(&self, distance: usize, name: &AstToken) -> Result<Value, GloxError> {
+        self.lookup_at(distance, &name.lexeme)
+            .ok_or_else(|| undefined_variable(name))
+    }
+
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &AstToken,
+        value: Value,
+    ) -> Result<(), GloxError> {
+        if distance == 0 {
+            return match self.values.get_mut(&*name.lexeme) {
+                Some(slot) => {
+                    *slot = value;
+                    Ok(())
+                }
+                None => Err(undefined_variable(name)),
+            };
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            None => Err(undefined_variable(name)),
+        }
+    }
+
+    // Every variable defined directly in this environment, sorted by name
+    pub fn bindings(&self) -> Vec<(String, Value)> {
+        let mut bindings: Vec<(String, Value)> = self
+            .values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
 
-impl Visitor for Interpreter {
-    fn visit_literal(&self, expr: &LiteralExpr) -> Result<Literal, GloxError> {
-        match &expr.value {
-            Literal::Bool(b) => Ok(Literal::Bool(*b)),
-            Literal::Float(f) => Ok(Literal::Float(*f)),
-            Literal::Str(s) => Ok(Literal::Str(s.clone())),
-            Literal::Nil => Ok(Literal::Nil),
+    // Unlike define, assignment is not allowed to create a new variable
+    pub fn assign(&mut self, name: &AstToken, value: Value) -> Result<(), GloxError> {
+        if let Some(slot) = self.values.get_mut(&*name.lexeme) {
+            *slot = value;
+            return Ok(());
         }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined_variable(name)),
+        }
+    }
+}
+
+fn undefined_variable(name: &AstToken) -> GloxError {
+    GloxError::runtime(format!("Undefined variable '{}'.", name.lexeme), name.span).with_help(
+        format!("declare it first with `var {} = ...;`", name.lexeme),
+    )
+}
+
+pub struct Interpreter {
+    // The outermost environment, variables the resolver left unresolved live here
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // How many calls to Lox functions and classes are running
+    call_depth: usize,
+}
+
+// Why statements stopped running early. A return unwinds to the call it's in,
+// where LoxFunction::call turns it back into the call's value, so only errors
+// ever make it out of the interpreter.
+pub enum Unwind {
+    Error(GloxError),
+    Return(Value),
+}
+
+impl From<GloxError> for Unwind {
+    fn from(error: GloxError) -> Unwind {
+        Unwind::Error(error)
     }
+}
 
-    fn visit_grouping(&self, expr: &Grouping) -> Result<Literal, GloxError> {
-        return self.evaluate(&expr.expression);
+// Every Lox call recurses on the native stack. When less than RED_ZONE is left
+// another STACK_GROWTH is allocated on the heap, so only MAX_CALL_DEPTH stops
+// deep recursion, however little stack the host thread has.
+const RED_ZONE: usize = 100 * 1024;
+const STACK_GROWTH: usize = 1024 * 1024;
+
+impl Visitor<Result<Value, GloxError>> for Interpreter {
+    fn visit_literal(&mut self, expr: &LiteralExpr) -> Result<Value, GloxError> {
+        Ok(Value::from(&expr.value))
     }
 
-    fn visit_unary(&self, expr: &Unary) -> Result<Literal, GloxError> {
-        let right: Literal = self.evaluate(&expr.right)?;
+    fn visit_grouping(&mut self, expr: &Grouping) -> Result<Value, GloxError> {
+        self.evaluate(&expr.expression)
+    }
+
+    fn visit_unary(&mut self, expr: &Unary) -> Result<Value, GloxError> {
+        let right: Value = self.evaluate(&expr.right)?;
+        let span: Span = expr.operator.span.to(expr.right.span());
         match &expr.operator.token_type {
-            TokenType::Minus => match right {
-                Literal::Float(f) => return Ok(Literal::Float(-1.0 * f)),
-                _ => Err(GloxError::RuntimeError(
-                    "Tried to minus something other than a number big bro".to_string(),
-                )),
-            },
-            TokenType::Bang => return Ok(Literal::Bool(!self.is_truthy(right))),
-            _ => Err(GloxError::RuntimeError(
-                "Called visit_unary on something that isn't a unary expression".to_string(),
+            TokenType::Minus => negate(right, span),
+            TokenType::Bang => Ok(Value::Bool(!right.is_truthy())),
+            _ => Err(GloxError::runtime(
+                "Called visit_unary on something that isn't a unary expression",
+                span,
             )),
         }
     }
 
-    fn visit_binary(&self, expr: &Binary) -> Result<Literal, GloxError> {
-        let left: Literal = self.evaluate(&expr.left)?;
-        let right: Literal = self.evaluate(&expr.right)?;
+    fn visit_binary(&mut self, expr: &Binary) -> Result<Value, GloxError> {
+        let left: Value = self.evaluate(&expr.left)?;
+        let right: Value = self.evaluate(&expr.right)?;
+        let span: Span = expr.left.span().to(expr.right.span());
 
-        match &expr.operator.token_type {
-            TokenType::Minus => match (left, right) {
-                (Literal::Float(l), Literal::Float(r)) => Ok(Literal::Float(l - r)),
-                _ => Err(GloxError::RuntimeError(
-                    "Tried to subtract something other than two floats".to_string(),
-                )),
-            },
-            TokenType::Plus => match (left, right) {
-                (Literal::Float(l), Literal::Float(r)) => Ok(Literal::Float(l + r)),
-                (Literal::Str(l), Literal::Str(r)) => Ok(Literal::Str(l + &r)),
-                _ => Err(GloxError::RuntimeError(
-                    "Tried to add two things that weren't either both floats or strings"
-                        .to_string(),
-                )),
-            },
-            TokenType::Slash => match (left, right) {
-                (Literal::Float(l), Literal::Float(r)) => Ok(Literal::Float(l / r)),
-                _ => Err(GloxError::RuntimeError(
-                    "You can only do / between two floats big bro".to_string(),
-                )),
-            },
-            TokenType::Star => match (left, right) {
-                (Literal::Float(l), Literal::Float(r)) => Ok(Literal::Float(l * r)),
-                _ => Err(GloxError::RuntimeError(
-                    "You can only do * between two floats big bro".to_string(),
-                )),
-            },
-            TokenType::Greater => match (left, right) {
-                (Literal::Float(l), Literal::Float(r)) => Ok(Literal::Bool(l > r)),
-                _ => Err(GloxError::RuntimeError(
-                    "You can only do > between two floats big bro".to_string(),
-                )),
-            },
-            TokenType::GreaterEqual => match (left, right) {
-                (Literal::Float(l), Literal::Float(r)) => Ok(Literal::Bool(l >= r)),
-                _ => Err(GloxError::RuntimeError(
-                    "You can only do >= for two floats big bro".to_string(),
-                )),
-            },
+        binary(expr.operator.token_type, left, right, span)
+    }
 
-            TokenType::Less => match (left, right) {
-                (Literal::Float(l), Literal::Float(r)) => Ok(Literal::Bool(l < r)),
-                _ => Err(GloxError::RuntimeError(
-                    "You can only do < for two floats big bro".to_string(),
-                )),
-            },
-            TokenType::LessEqual => match (left, right) {
-                (Literal::Float(l), Literal::Float(r)) => Ok(Literal::Bool(l < r)),
-                _ => Err(GloxError::RuntimeError(
-                    "You can only do < for two floats big bro".to_string(),
-                )),
-            },
-            TokenType::BangEqual => Ok(Literal::Bool(!self.is_equal(left, right))),
-            TokenType::EqualEqual => Ok(Literal::Bool(self.is_equal(left, right))),
-            _ => Err(GloxError::RuntimeError(
-                "Tried to call visit_binary on something that isn't a binary expression"
-                    .to_string(),
+    fn visit_variable(&mut self, expr: &Variable) -> Result<Value, GloxError> {
+        self.look_up_variable(&expr.name, &expr.depth)
+    }
+
+    fn visit_assign(&mut self, expr: &Assign) -> Result<Value, GloxError> {
+        let value: Value = self.evaluate(&expr.value)?;
+        match expr.depth.get() {
+            Some(distance) => {
+                self.environment
+                    .borrow_mut()
+                    .assign_at(distance, &expr.name, value.clone())?
+            }
+            None => self
+                .globals
+                .borrow_mut()
+                .assign(&expr.name, value.clone())?,
+        }
+        Ok(value)
+    }
+
+    // Logical operators return the operand that decided the result rather than a bool,
+    // and only evaluate the right operand when the left one doesn't already decide it
+    fn visit_logical(&mut self, expr: &Logical) -> Result<Value, GloxError> {
+        let left: Value = self.evaluate(&expr.left)?;
+        let left_truthy: bool = left.is_truthy();
+        match expr.operator.token_type {
+            TokenType::Or if left_truthy => Ok(left),
+            TokenType::And if !left_truthy => Ok(left),
+            _ => self.evaluate(&expr.right),
+        }
+    }
+
+    fn visit_call(&mut self, expr: &Call) -> Result<Value, GloxError> {
+        let callee: Value = self.evaluate(&expr.callee)?;
+        let mut arguments: Vec<Value> = Vec::with_capacity(expr.arguments.len());
+        for argument in &expr.arguments {
+            arguments.push(self.evaluate(argument)?);
+        }
+
+        let function: &dyn LoxCallable = match &callee {
+            Value::Function(function) => function.as_ref(),
+            Value::Native(native) => native.as_ref(),
+            Value::Class(class) => class,
+            _ => {
+                return Err(GloxError::runtime(
+                    "Can only call functions and classes.",
+                    expr.callee.span(),
+                ));
+            }
+        };
+        if arguments.len() != function.arity() {
+            return Err(GloxError::runtime(
+                format!(
+                    "Expected {} arguments but got {}.",
+                    function.arity(),
+                    arguments.len()
+                ),
+                expr.callee.span().to(expr.paren.span),
+            ));
+        }
+        if let Value::Native(_) = callee {
+            let result = function.call(self, arguments);
+            return result.map_err(|e| e.or_span(expr.callee.span().to(expr.paren.span)));
+        }
+        if self.call_depth + 1 >= MAX_CALL_DEPTH {
+            return Err(stack_overflow(expr.callee.span().to(expr.paren.span)));
+        }
+        self.call_depth += 1;
+        let result = stacker::maybe_grow(RED_ZONE, STACK_GROWTH, || function.call(self, arguments));
+        self.call_depth -= 1;
+        result
+    }
+
+    fn visit_get(&mut self, expr: &Get) -> Result<Value, GloxError> {
+        match self.evaluate(&expr.object)? {
+            Value::Instance(instance) => LoxInstance::get(&instance, &expr.name),
+            _ => Err(GloxError::runtime(
+                "Only instances have properties.",
+                expr.name.span,
             )),
         }
     }
+
+    fn visit_set(&mut self, expr: &Set) -> Result<Value, GloxError> {
+        let instance = match self.evaluate(&expr.object)? {
+            Value::Instance(instance) => instance,
+            _ => {
+                return Err(GloxError::runtime(
+                    "Only instances have fields.",
+                    expr.name.span,
+                ));
+            }
+        };
+        let value: Value = self.evaluate(&expr.value)?;
+        instance.borrow_mut().set(&expr.name, value.clone());
+        Ok(value)
+    }
+
+    fn visit_list(&mut self, expr: &ListExpr) -> Result<Value, GloxError> {
+        let mut elements: Vec<Value> = Vec::with_capacity(expr.elements.len());
+        for element in &expr.elements {
+            elements.push(self.evaluate(element)?);
+        }
+        Ok(Value::List(Rc::new(RefCell::new(elements))))
+    }
+
+    fn visit_map(&mut self, expr: &MapExpr) -> Result<Value, GloxError> {
+        let mut map = LoxMap::new();
+        for (key, value) in &expr.entries {
+            let key_value: Value = self.evaluate(key)?;
+            let value: Value = self.evaluate(value)?;
+            map.insert(key_value, value)
+                .map_err(|message| GloxError::runtime(message, key.span()))?;
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+
+    fn visit_index(&mut self, expr: &Index) -> Result<Value, GloxError> {
+        let object: Value = self.evaluate(&expr.object)?;
+        let index: Value = self.evaluate(&expr.index)?;
+        get_index(object, index, expr.object.span(), expr.index.span())
+    }
+
+    fn visit_set_index(&mut self, expr: &SetIndex) -> Result<Value, GloxError> {
+        let object: Value = self.evaluate(&expr.object)?;
+        let index: Value = self.evaluate(&expr.index)?;
+        let value: Value = self.evaluate(&expr.value)?;
+        set_index(
+            object,
+            index,
+            value.clone(),
+            expr.object.span(),
+            expr.index.span(),
+        )?;
+        Ok(value)
+    }
+
+    fn visit_interpolate(&mut self, expr: &Interpolate) -> Result<Value, GloxError> {
+        let mut string = String::new();
+        for part in &expr.parts {
+            string.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Value::Str(Rc::from(string)))
+    }
+
+    fn visit_this(&mut self, expr: &This) -> Result<Value, GloxError> {
+        self.look_up_variable(&expr.keyword, &expr.depth)
+    }
+
+    // `super` is bound in the environment surrounding the methods of a subclass,
+    // and `this` one level further in, once a method has been bound to an instance
+    fn visit_super(&mut self, expr: &Super) -> Result<Value, GloxError> {
+        let distance: usize = expr.depth.get().unwrap_or(0);
+        let superclass = match self.look_up_variable(&expr.keyword, &expr.depth)? {
+            Value::Class(class) => class,
+            _ => {
+                return Err(GloxError::runtime(
+                    "Superclass must be a class.",
+                    expr.keyword.span,
+                ));
+            }
+        };
+        let this = self
+            .environment
+            .borrow()
+            .lookup_at(distance.saturating_sub(1), "this");
+        let instance = match this {
+            Some(Value::Instance(instance)) => instance,
+            _ => {
+                return Err(GloxError::runtime(
+                    "Can't use 'super' outside of a method.",
+                    expr.keyword.span,
+                ));
+            }
+        };
+        match superclass.find_method(&expr.method.lexeme) {
+            Some(method) => Ok(Value::Function(Rc::new(method.bind(instance)))),
+            None => Err(GloxError::runtime(
+                format!("Undefined property '{}'.", expr.method.lexeme),
+                expr.method.span,
+            )),
+        }
+    }
+}
+
+impl StmtVisitor<Result<(), Unwind>> for Interpreter {
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) -> Result<(), Unwind> {
+        self.evaluate(&stmt.expression)?;
+        Ok(())
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> Result<(), Unwind> {
+        let value: Value = self.evaluate(&stmt.expression)?;
+        println!("{}", value);
+        Ok(())
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> Result<(), Unwind> {
+        let value: Value = match &stmt.initializer {
+            Some(initializer) => self.evaluate(initializer)?,
+            None => Value::Nil,
+        };
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name.lexeme, value);
+        Ok(())
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> Result<(), Unwind> {
+        let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+        self.execute_block(&stmt.statements, Rc::new(RefCell::new(environment)))
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> Result<(), Unwind> {
+        let condition: Value = self.evaluate(&stmt.condition)?;
+        if condition.is_truthy() {
+            self.execute(&stmt.then_branch)?;
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)?;
+        }
+        Ok(())
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Rc<FunctionStmt>) -> Result<(), Unwind> {
+        let function = LoxFunction::new(Rc::clone(stmt), Rc::clone(&self.environment), false);
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name.lexeme, Value::Function(Rc::new(function)));
+        Ok(())
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> Result<(), Unwind> {
+        let value: Value = match &stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> Result<(), Unwind> {
+        let superclass: Option<Rc<LoxClass>> = match &stmt.superclass {
+            Some(variable) => match self.visit_variable(variable)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    return Err(GloxError::runtime(
+                        "Superclass must be a class.",
+                        variable.name.span,
+                    )
+                    .into());
+                }
+            },
+            None => None,
+        };
+
+        // Defining the name before creating the class lets methods refer to it
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name.lexeme, Value::Nil);
+
+        // Subclass methods close over an extra environment that holds `super`
+        let mut method_environment = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::new_enclosed(Rc::clone(&self.environment));
+            environment.define("super", Value::Class(Rc::clone(superclass)));
+            method_environment = Rc::new(RefCell::new(environment));
+        }
+
+        let mut methods: HashMap<String, Rc<LoxFunction>> = HashMap::new();
+        for method in &stmt.methods {
+            let function = LoxFunction::new(
+                Rc::clone(method),
+                Rc::clone(&method_environment),
+                &*method.name.lexeme == "init",
+            );
+            methods.insert(method.name.lexeme.to_string(), Rc::new(function));
+        }
+
+        let class = LoxClass::new(stmt.name.lexeme.to_string(), superclass, methods);
+        self.environment
+            .borrow_mut()
+            .assign(&stmt.name, Value::Class(Rc::new(class)))?;
+        Ok(())
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> Result<(), Unwind> {
+        loop {
+            let condition: Value = self.evaluate(&stmt.condition)?;
+            if !condition.is_truthy() {
+                break;
+            }
+            self.execute(&stmt.body)?;
+        }
+        Ok(())
+    }
+
+    // The loop runs in its own scope so a variable declared in the
+    // initializer doesn't outlive it
+    fn visit_for_stmt(&mut self, stmt: &ForStmt) -> Result<(), Unwind> {
+        let environment = Environment::new_enclosed(Rc::clone(&self.environment));
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = self.run_for(stmt);
+        self.environment = previous;
+        result
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
-        Interpreter {}
+        let mut globals = Environment::new();
+        stdlib::define_globals(&mut globals);
+        let globals = Rc::new(RefCell::new(globals));
+        Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            call_depth: 0,
+        }
     }
 
-    pub fn interpret(&self, expr: &Expr) -> Result<Literal, GloxError> {
-        let value: Literal = self.evaluate(expr)?;
-        Ok(value)
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.globals.borrow().bindings()
     }
 
-    fn evaluate(&self, expr: &Expr) -> Result<Literal, GloxError> {
-        let value: Literal = expr.accept(self.clone())?;
-        Ok(value)
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().lookup_at(0, name)
     }
 
-    fn is_truthy(&self, value: Literal) -> bool {
-        match value {
-            Literal::Nil => {
-                return false;
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().define(name, value);
+    }
+
+    // Executes each statement in order, stopping at the first runtime error
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), GloxError> {
+        for statement in statements {
+            match self.execute(statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(e)) => return Err(e),
+                Err(Unwind::Return(_)) => unreachable!("the resolver rejects top-level returns"),
             }
-            Literal::Bool(b) => return b,
-            _ => return true,
         }
+        Ok(())
     }
 
-    fn is_equal(&self, l: Literal, r: Literal) -> bool {
-        match (l, r) {
-            (Literal::Float(a), Literal::Float(b)) => a == b,
-            (Literal::Str(a), Literal::Str(b)) => a == b,
-            (Literal::Bool(a), Literal::Bool(b)) => a == b,
-            (Literal::Nil, Literal::Nil) => true,
-            _ => false,
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        stmt.accept(self)
+    }
+
+    // Runs the statements inside the given environment, restoring the
+    // previous environment afterwards even if a statement fails
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = statements.iter().try_for_each(|s| self.execute(s));
+        self.environment = previous;
+        result
+    }
+
+    fn run_for(&mut self, stmt: &ForStmt) -> Result<(), Unwind> {
+        if let Some(initializer) = &stmt.initializer {
+            self.execute(initializer)?;
         }
+        loop {
+            if let Some(condition) = &stmt.condition {
+                let condition: Value = self.evaluate(condition)?;
+                if !condition.is_truthy() {
+                    break;
+                }
+            }
+            self.execute(&stmt.body)?;
+            if let Some(increment) = &stmt.increment {
+                self.evaluate(increment)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn look_up_variable(&self, name: &AstToken, depth: &Depth) -> Result<Value, GloxError> {
+        match depth.get() {
+            Some(distance) => self.environment.borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, GloxError> {
+        let value: Value = expr.accept(self)?;
+        Ok(value)
     }
 }
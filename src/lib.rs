@@ -0,0 +1,30 @@
+// glox, an interpreter for Lox with two backends: a bytecode VM and the
+// tree-walker it grew out of. Glox is the way in, both for the binary and for
+// Rust programs embedding the language.
+mod ast_printer;
+mod chunk;
+mod class;
+mod compiler;
+mod error;
+mod expr;
+mod formatter;
+mod function;
+mod glox;
+mod interpreter;
+mod map;
+mod native;
+mod object;
+mod operators;
+mod parser;
+mod resolver;
+mod scanner;
+mod stdlib;
+mod stmt;
+mod token;
+mod vm;
+
+pub use error::{Diagnostic, GloxError};
+pub use glox::{Backend, Glox};
+pub use native::{FromValue, IntoNative, IntoValue, NativeResult};
+pub use object::{NativeFunction, Value};
+pub use token::{Literal, Span, Token, TokenType};
@@ -1,24 +1,124 @@
-use std::env;
-use glox::Glox;
+use std::io::Read;
+use std::{env, fs, io, process};
+use glox::{Backend, Glox, GloxError, Token, TokenType};
+use repl::Repl;
 
-mod glox;
-mod token;
-mod scanner;
-mod expr;
-mod parser;
-mod error;
-mod interpreter;
+mod repl;
+
+// sysexits-style exit codes, so scripts calling glox can tell failures apart
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_NOINPUT: i32 = 66;
+const EX_SOFTWARE: i32 = 70;
+
+const USAGE: &str = "\
+Usage: glox [command] [--tree-walker] [<file> | - | -e <code>]
+
+Commands:
+  run     run a script, the default when given a file
+  repl    start the interactive prompt, the default with no arguments
+  check   parse and resolve a script without running it
+  tokens  print the tokens the scanner produces
+  ast     print the syntax tree the parser produces
+  fmt     print the script reformatted, if it has no comments
+  disasm  print the bytecode the compiler produces
+
+Pass - to read the script from stdin, or -e to give it inline. Scripts
+run on the bytecode VM, --tree-walker runs them on the tree-walking
+interpreter instead.
+
+Exit codes: 64 bad usage, 65 syntax, resolution or compile error, 66
+unreadable input, 70 runtime error.";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        panic!("Usage: glox program.lox")
-    } else if args.len() == 2 {
-        let filepath: &String = &args[1];
-        let mut glox = Glox::new_from_file(filepath);
-        glox.run_file(filepath);
-    } else {
-        let mut glox = Glox::new_for_prompt();
-        glox.run_prompt();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let backend = match args.iter().position(|arg| arg == "--tree-walker") {
+        Some(i) => {
+            args.remove(i);
+            Backend::TreeWalker
+        }
+        None => Backend::Vm,
+    };
+    let (command, rest) = match args.first().map(String::as_str) {
+        None => ("repl", &args[..]),
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            return;
+        }
+        Some(command @ ("run" | "repl" | "check" | "tokens" | "ast" | "fmt" | "disasm")) => {
+            (command, &args[1..])
+        }
+        Some(_) => ("run", &args[..]),
+    };
+
+    if command == "repl" {
+        if !rest.is_empty() {
+            usage_error();
+        }
+        Repl::new(backend).run();
+        return;
+    }
+
+    let (source, filename) = match rest {
+        [flag, code] if flag == "-e" => (code.clone(), "<eval>".to_string()),
+        [path] if path == "-" => {
+            let mut source = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut source) {
+                eprintln!("Error: failed to read stdin: {}", e);
+                process::exit(EX_NOINPUT);
+            }
+            (source, "<stdin>".to_string())
+        }
+        [path] if !path.starts_with('-') => match fs::read_to_string(path) {
+            Ok(source) => (source, path.clone()),
+            Err(e) => {
+                eprintln!("Error: failed to read {}: {}", path, e);
+                process::exit(EX_NOINPUT);
+            }
+        },
+        _ => usage_error(),
+    };
+
+    let result = match command {
+        "check" => Glox::check(&source),
+        "tokens" => Glox::tokenize(&source).map(|tokens| print_tokens(&tokens)),
+        "ast" => Glox::syntax_tree(&source).map(|tree| println!("{}", tree)),
+        "fmt" => Glox::format(&source).map(|code| print!("{}", code)),
+        "disasm" => Glox::disassemble(&source).map(|listing| print!("{}", listing)),
+        _ => Glox::with_backend(backend).run(&source),
+    };
+    if let Err(errors) = result {
+        report(&errors, &source, &filename);
+        // A runtime error is only ever returned on its own
+        let code = match errors.first() {
+            Some(GloxError::RuntimeError(_)) => EX_SOFTWARE,
+            _ => EX_DATAERR,
+        };
+        process::exit(code);
+    }
+}
+
+fn usage_error() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(EX_USAGE);
+}
+
+fn print_tokens(tokens: &[Token]) {
+    for token in tokens {
+        let position = format!("{}:{}", token.span.line, token.span.column);
+        let token_type = format!("{:?}", token.token_type);
+        match token.token_type {
+            TokenType::String | TokenType::Interpolation | TokenType::Number => println!(
+                "{:<6} {:<12} {:<12} {}",
+                position, token_type, token.lexeme, token.literal
+            ),
+            _ => println!("{:<6} {:<12} {}", position, token_type, token.lexeme),
+        }
+    }
+}
+
+fn report(errors: &[GloxError], source: &str, filename: &str) {
+    for e in errors {
+        eprintln!("{}", e.render(source, filename));
     }
 }
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::object::{Quoted, Value, exact_int};
+
+// A Lox map. Entries stay in the order their keys were first inserted, which is
+// the order keys() and values() return them in and the order a map prints in.
+// Setting a key that's already there keeps its place.
+#[derive(Debug, Default)]
+pub struct LoxMap {
+    entries: Vec<(Value, Value)>,
+    // Where each key's entry is in entries
+    indices: HashMap<Key, usize>,
+}
+
+// What a key is hashed as. Keys that are == to each other have to hash the same,
+// so a float that's exactly equal to an int is the same key as that int.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Key {
+    Int(i64),
+    Float(u64),
+    Str(Rc<str>),
+    Bool(bool),
+    Nil,
+}
+
+impl Key {
+    fn new(value: &Value) -> Result<Key, String> {
+        match value {
+            Value::Int(i) => Ok(Key::Int(*i)),
+            Value::Float(x) if x.is_nan() => {
+                Err("nan can't be a map key, it isn't equal to itself.".to_string())
+            }
+            Value::Float(x) => match exact_int(*x) {
+                Some(i) => Ok(Key::Int(i)),
+                None => Ok(Key::Float(x.to_bits())),
+            },
+            Value::Str(s) => Ok(Key::Str(Rc::clone(s))),
+            Value::Bool(b) => Ok(Key::Bool(*b)),
+            Value::Nil => Ok(Key::Nil),
+            value => Err(format!(
+                "Only strings, numbers, bools and nil can be map keys, not {}.",
+                value.type_name()
+            )),
+        }
+    }
+}
+
+impl LoxMap {
+    pub fn new() -> LoxMap {
+        LoxMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<Value>, String> {
+        let key = Key::new(key)?;
+        Ok(self.indices.get(&key).map(|&i| self.entries[i].1.clone()))
+    }
+
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<(), String> {
+        match self.indices.get(&Key::new(&key)?) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.indices.insert(Key::new(&key)?, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    // Everything after the removed entry moves up one to keep the order
+    pub fn remove(&mut self, key: &Value) -> Result<Option<Value>, String> {
+        let Some(removed) = self.indices.remove(&Key::new(key)?) else {
+            return Ok(None);
+        };
+        for i in self.indices.values_mut() {
+            if *i > removed {
+                *i -= 1;
+            }
+        }
+        Ok(Some(self.entries.remove(removed).1))
+    }
+
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+
+    pub fn keys(&self) -> Vec<Value> {
+        self.entries.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    pub fn values(&self) -> Vec<Value> {
+        self.entries
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+// The error for looking up a key that isn't there
+pub fn missing_key(key: &Value) -> String {
+    format!("Key {} isn't in the map.", Quoted(key))
+}
@@ -0,0 +1,261 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::error::GloxError;
+use crate::map::LoxMap;
+use crate::object::{NativeFunction, Value, exact_int};
+
+// Conversions between runtime values and Rust types, so host functions can be
+// written as ordinary closures like `|x: f64, y: f64| x.max(y)` and registered
+// with Glox::register. Arguments that don't convert are runtime errors in the
+// script, pointing at the call.
+
+// A Rust type a Lox argument can be converted into
+pub trait FromValue: Sized {
+    // What kind of value was expected, for error messages
+    const EXPECTED: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+// A Rust type that can be handed back to Lox
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+// What a host function can return: either a value, or a Result whose error
+// becomes a runtime error in the script
+pub trait NativeResult {
+    fn into_result(self) -> Result<Value, GloxError>;
+}
+
+// Implemented for closures taking up to five FromValue arguments, Args is the
+// tuple of argument types and only there to tell the implementations apart
+pub trait IntoNative<Args> {
+    fn into_native(self, name: &str) -> NativeFunction;
+}
+
+impl FromValue for Value {
+    const EXPECTED: &'static str = "any value";
+
+    fn from_value(value: &Value) -> Option<Value> {
+        Some(value.clone())
+    }
+}
+
+// Ints are promoted, like they are by arithmetic
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: &Value) -> Option<f64> {
+        match value {
+            Value::Int(i) => Some(*i as f64),
+            Value::Float(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+// Floats convert only if they're whole and in range, so 2.0 is fine but 2.5 isn't
+impl FromValue for i64 {
+    const EXPECTED: &'static str = "an integer";
+
+    fn from_value(value: &Value) -> Option<i64> {
+        match value {
+            Value::Int(i) => Some(*i),
+            Value::Float(x) => exact_int(*x),
+            _ => None,
+        }
+    }
+}
+
+// Only true and false convert, not every truthy value
+impl FromValue for bool {
+    const EXPECTED: &'static str = "a bool";
+
+    fn from_value(value: &Value) -> Option<bool> {
+        match value {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Rc<str> {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: &Value) -> Option<Rc<str>> {
+        match value {
+            Value::Str(s) => Some(Rc::clone(s)),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: &Value) -> Option<String> {
+        match value {
+            Value::Str(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+// The list itself rather than a copy, so natives can change it in place
+impl FromValue for Rc<RefCell<Vec<Value>>> {
+    const EXPECTED: &'static str = "a list";
+
+    fn from_value(value: &Value) -> Option<Rc<RefCell<Vec<Value>>>> {
+        match value {
+            Value::List(list) => Some(Rc::clone(list)),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Rc<RefCell<LoxMap>> {
+    const EXPECTED: &'static str = "a map";
+
+    fn from_value(value: &Value) -> Option<Rc<RefCell<LoxMap>>> {
+        match value {
+            Value::Map(map) => Some(Rc::clone(map)),
+            _ => None,
+        }
+    }
+}
+
+// For optional arguments, nil converts to None
+impl<T: FromValue> FromValue for Option<T> {
+    const EXPECTED: &'static str = T::EXPECTED;
+
+    fn from_value(value: &Value) -> Option<Option<T>> {
+        match value {
+            Value::Nil => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Int(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for Rc<str> {
+    fn into_value(self) -> Value {
+        Value::Str(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::Str(self.into())
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::Str(self.into())
+    }
+}
+
+impl IntoValue for Vec<Value> {
+    fn into_value(self) -> Value {
+        Value::List(Rc::new(RefCell::new(self)))
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Nil
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(value) => value.into_value(),
+            None => Value::Nil,
+        }
+    }
+}
+
+impl<T: IntoValue> NativeResult for T {
+    fn into_result(self) -> Result<Value, GloxError> {
+        Ok(self.into_value())
+    }
+}
+
+impl<T: IntoValue, E: fmt::Display> NativeResult for Result<T, E> {
+    fn into_result(self) -> Result<Value, GloxError> {
+        self.map(IntoValue::into_value)
+            .map_err(|e| GloxError::native(e.to_string()))
+    }
+}
+
+fn argument<T: FromValue>(name: &str, arguments: &[Value], index: usize) -> Result<T, GloxError> {
+    let value = &arguments[index];
+    T::from_value(value).ok_or_else(|| {
+        GloxError::native(format!(
+            "Expected {} for argument {} of '{}' but got {}.",
+            T::EXPECTED,
+            index + 1,
+            name,
+            value.type_name()
+        ))
+    })
+}
+
+macro_rules! impl_into_native {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: NativeResult,
+            $($arg: FromValue),*
+        {
+            #[allow(non_snake_case, unused_assignments, unused_mut, unused_variables)]
+            fn into_native(self, name: &str) -> NativeFunction {
+                let arity = <[&str]>::len(&[$(stringify!($arg)),*]);
+                let function_name = name.to_string();
+                NativeFunction::new(name, arity, move |arguments| {
+                    let mut index = 0;
+                    $(
+                        let $arg: $arg = argument(&function_name, arguments, index)?;
+                        index += 1;
+                    )*
+                    self($($arg),*).into_result()
+                })
+            }
+        }
+    };
+}
+
+impl_into_native!();
+impl_into_native!(A);
+impl_into_native!(A, B);
+impl_into_native!(A, B, C);
+impl_into_native!(A, B, C, D);
+impl_into_native!(A, B, C, D, E);
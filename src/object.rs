@@ -0,0 +1,284 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::class::{LoxClass, LoxInstance};
+use crate::error::GloxError;
+use crate::function::{LoxCallable, LoxFunction};
+use crate::interpreter::Interpreter;
+use crate::map::LoxMap;
+use crate::token::Literal;
+use crate::vm::{BoundMethod, Closure, VmClass, VmInstance};
+
+// Value is everything a Lox program can compute with at runtime. Token literals
+// are converted into values when the interpreter reaches them, everything else
+// (functions, classes, instances) only ever exists at runtime.
+//
+// Values are cheap to clone: anything larger than a word lives behind an Rc and
+// is shared, which also gives instances, lists and maps their reference semantics.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(Rc<str>),
+    Bool(bool),
+    Nil,
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<LoxMap>>),
+    // The VM's own functions, classes and instances, see vm.rs
+    Closure(Rc<Closure>),
+    BoundMethod(Rc<BoundMethod>),
+    VmClass(Rc<VmClass>),
+    VmInstance(Rc<RefCell<VmInstance>>),
+}
+
+impl Value {
+    // The name scripts see for the kind of value, also used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Str(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Nil => "nil",
+            Value::Function(_) | Value::Native(_) | Value::Closure(_) | Value::BoundMethod(_) => {
+                "function"
+            }
+            Value::Class(_) | Value::VmClass(_) => "class",
+            Value::Instance(_) | Value::VmInstance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+
+    // Only nil and false are falsey
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    // What == means. Ints and floats compare by their exact value, strings by their contents,
+    // and everything else (lists and maps included) only equals itself.
+    pub fn is_equal(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            // Exact, so an int that rounds to a float when converted isn't equal to it
+            (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) => {
+                exact_int(*b) == Some(*a)
+            }
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            (Value::Closure(a), Value::Closure(b)) => Rc::ptr_eq(a, b),
+            (Value::BoundMethod(a), Value::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            (Value::VmClass(a), Value::VmClass(b)) => Rc::ptr_eq(a, b),
+            (Value::VmInstance(a), Value::VmInstance(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+// Where index points in a list of length len, counting back from the end when
+// it's negative, or None if it's out of bounds
+pub fn list_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 {
+        index.checked_add(len as i64)?
+    } else {
+        index
+    };
+    usize::try_from(index).ok().filter(|&i| i < len)
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Value {
+        match literal {
+            Literal::Int(i) => Value::Int(*i),
+            Literal::Float(f) => Value::Float(*f),
+            Literal::Str(s) => Value::Str(Rc::from(s.as_str())),
+            Literal::Bool(b) => Value::Bool(*b),
+            Literal::Nil => Value::Nil,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", format_number(*x)),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Nil => write!(f, "nil"),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Native(native) => write!(f, "{}", native),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Value::Closure(closure) => write!(f, "{}", closure),
+            Value::BoundMethod(method) => write!(f, "{}", method),
+            Value::VmClass(class) => write!(f, "{}", class),
+            Value::VmInstance(instance) => write!(f, "{}", instance.borrow()),
+            Value::List(list) => write_once(f, Rc::as_ptr(list) as *const (), "[...]", |f| {
+                write_elements(f, &list.borrow())
+            }),
+            Value::Map(map) => write_once(f, Rc::as_ptr(map) as *const (), "{...}", |f| {
+                write_entries(f, &map.borrow())
+            }),
+        }
+    }
+}
+
+// A value as it appears inside a list or map, where strings are quoted so that
+// ["a, b"] and ["a", "b"] can be told apart
+pub struct Quoted<'a>(pub &'a Value);
+
+impl fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Value::Str(s) => write!(f, "\"{}\"", s),
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+thread_local! {
+    // The lists and maps being printed, outermost first, so one that contains
+    // itself prints as [...] or {...} the second time round instead of recursing forever
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+fn write_once(
+    f: &mut fmt::Formatter<'_>,
+    pointer: *const (),
+    placeholder: &str,
+    write: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+) -> fmt::Result {
+    if PRINTING.with_borrow(|printing| printing.contains(&pointer)) {
+        return write!(f, "{}", placeholder);
+    }
+    PRINTING.with_borrow_mut(|printing| printing.push(pointer));
+    let result = write(f);
+    PRINTING.with_borrow_mut(|printing| printing.pop());
+    result
+}
+
+fn write_elements(f: &mut fmt::Formatter<'_>, elements: &[Value]) -> fmt::Result {
+    write!(f, "[")?;
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", Quoted(element))?;
+    }
+    write!(f, "]")
+}
+
+fn write_entries(f: &mut fmt::Formatter<'_>, map: &LoxMap) -> fmt::Result {
+    write!(f, "{{")?;
+    for (i, (key, value)) in map.entries().iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}: {}", Quoted(key), Quoted(value))?;
+    }
+    write!(f, "}}")
+}
+
+// The int a float is exactly equal to, if there is one. Whole floats in
+// [-2^63, 2^63) convert exactly, as i64::MAX rounds up to 2^63 as a float.
+pub fn exact_int(x: f64) -> Option<i64> {
+    let in_range = x >= i64::MIN as f64 && x < i64::MAX as f64;
+    if in_range && x as i64 as f64 == x {
+        Some(x as i64)
+    } else {
+        None
+    }
+}
+
+// Numbers print like they do in clox: whole numbers without a trailing `.0`,
+// and nan, inf and -inf for the special values. Otherwise a number prints
+// with as few digits as it takes to read back the same value, switching to
+// exponent form (`1e+21`, `1e-7`) outside of 1e-7 to 1e21 like JavaScript.
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "nan".to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let magnitude = n.abs();
+    if magnitude != 0.0 && !(1e-7..1e21).contains(&magnitude) {
+        let text = format!("{:e}", n);
+        return match text.split_once('e') {
+            Some((mantissa, exponent)) if !exponent.starts_with('-') => {
+                format!("{}e+{}", mantissa, exponent)
+            }
+            _ => text,
+        };
+    }
+    n.to_string()
+}
+
+type NativeFn = dyn Fn(&[Value]) -> Result<Value, GloxError>;
+
+// A function implemented in Rust. The interpreter checks the arity before calling
+// it, so the implementation can index into its arguments directly.
+pub struct NativeFunction {
+    pub name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, GloxError> + 'static,
+    ) -> NativeFunction {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    // Used by the VM, which calls natives without an Interpreter
+    pub fn call_with(&self, arguments: &[Value]) -> Result<Value, GloxError> {
+        (self.function)(arguments)
+    }
+}
+
+impl LoxCallable for NativeFunction {
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        &self,
+        _interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, GloxError> {
+        (self.function)(&arguments)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
@@ -0,0 +1,268 @@
+use std::cmp::Ordering;
+
+use crate::error::GloxError;
+use crate::map::missing_key;
+use crate::object::{Value, list_index};
+use crate::token::{Span, TokenType};
+
+// What the operators do to values, shared by the tree-walker and the VM so the
+// two backends can't drift apart. Spans are where errors point.
+
+pub fn negate(right: Value, span: Span) -> Result<Value, GloxError> {
+    match right {
+        Value::Int(i) => checked(i.checked_neg(), span),
+        Value::Float(f) => Ok(Value::Float(-f)),
+        _ => Err(GloxError::runtime(
+            "Tried to minus something other than a number big bro",
+            span,
+        )),
+    }
+}
+
+// Two ints give an int, except for / which always gives a float. An int with
+// a float is promoted to a float.
+pub fn binary(
+    operator: TokenType,
+    left: Value,
+    right: Value,
+    span: Span,
+) -> Result<Value, GloxError> {
+    match operator {
+        TokenType::Minus => match numbers(&left, &right) {
+            Some(Numbers::Ints(l, r)) => checked(l.checked_sub(r), span),
+            Some(Numbers::Floats(l, r)) => Ok(Value::Float(l - r)),
+            None => Err(GloxError::runtime(
+                "Tried to subtract something other than two numbers",
+                span,
+            )),
+        },
+        TokenType::Plus => match numbers(&left, &right) {
+            Some(Numbers::Ints(l, r)) => checked(l.checked_add(r), span),
+            Some(Numbers::Floats(l, r)) => Ok(Value::Float(l + r)),
+            None => match (left, right) {
+                (Value::Str(l), Value::Str(r)) => Ok(Value::Str(format!("{l}{r}").into())),
+                _ => Err(GloxError::runtime(
+                    "Tried to add two things that weren't either both numbers or strings",
+                    span,
+                )),
+            },
+        },
+        TokenType::Slash => match numbers(&left, &right) {
+            Some(Numbers::Ints(l, r)) => Ok(Value::Float(l as f64 / r as f64)),
+            Some(Numbers::Floats(l, r)) => Ok(Value::Float(l / r)),
+            None => Err(GloxError::runtime(
+                "You can only do / between two numbers big bro",
+                span,
+            )),
+        },
+        TokenType::Star => match numbers(&left, &right) {
+            Some(Numbers::Ints(l, r)) => checked(l.checked_mul(r), span),
+            Some(Numbers::Floats(l, r)) => Ok(Value::Float(l * r)),
+            None => Err(GloxError::runtime(
+                "You can only do * between two numbers big bro",
+                span,
+            )),
+        },
+        // Both round towards negative infinity, so a % b has the sign of b
+        // and (a ~/ b) * b + a % b == a
+        TokenType::TildeSlash => match numbers(&left, &right) {
+            Some(Numbers::Ints(_, 0)) => Err(division_by_zero(span)),
+            Some(Numbers::Ints(l, r)) => {
+                let quotient = l.checked_div(r);
+                let rounded_up = l.wrapping_rem(r) != 0 && (l < 0) != (r < 0);
+                checked(quotient.map(|q| if rounded_up { q - 1 } else { q }), span)
+            }
+            Some(Numbers::Floats(l, r)) => Ok(Value::Float((l / r).floor())),
+            None => Err(GloxError::runtime(
+                "You can only do ~/ between two numbers big bro",
+                span,
+            )),
+        },
+        TokenType::Percent => match numbers(&left, &right) {
+            Some(Numbers::Ints(_, 0)) => Err(division_by_zero(span)),
+            // i64::MIN % -1 overflows in Rust but the answer is just 0
+            Some(Numbers::Ints(l, r)) => {
+                let m = l.wrapping_rem(r);
+                Ok(Value::Int(if m != 0 && (m < 0) != (r < 0) {
+                    m + r
+                } else {
+                    m
+                }))
+            }
+            Some(Numbers::Floats(l, r)) => {
+                let m = l % r;
+                Ok(Value::Float(if m != 0.0 && (m < 0.0) != (r < 0.0) {
+                    m + r
+                } else {
+                    m
+                }))
+            }
+            None => Err(GloxError::runtime(
+                "You can only do % between two numbers big bro",
+                span,
+            )),
+        },
+        TokenType::Greater => compare(&left, &right, ">", Ordering::is_gt, span),
+        TokenType::GreaterEqual => compare(&left, &right, ">=", Ordering::is_ge, span),
+        TokenType::Less => compare(&left, &right, "<", Ordering::is_lt, span),
+        TokenType::LessEqual => compare(&left, &right, "<=", Ordering::is_le, span),
+        TokenType::BangEqual => Ok(Value::Bool(!left.is_equal(&right))),
+        TokenType::EqualEqual => Ok(Value::Bool(left.is_equal(&right))),
+        _ => Err(GloxError::runtime(
+            "Tried to call visit_binary on something that isn't a binary expression",
+            span,
+        )),
+    }
+}
+
+// xs[i] for lists and m[key] for maps
+pub fn get_index(
+    object: Value,
+    index: Value,
+    object_span: Span,
+    index_span: Span,
+) -> Result<Value, GloxError> {
+    match object {
+        Value::List(list) => {
+            let i = element(&list.borrow(), &index, index_span)?;
+            Ok(list.borrow()[i].clone())
+        }
+        Value::Map(map) => {
+            let value = map.borrow().get(&index);
+            match value {
+                Ok(Some(value)) => Ok(value),
+                Ok(None) => Err(GloxError::runtime(missing_key(&index), index_span)
+                    .with_help("use has(map, key) to check whether a key is there")),
+                Err(message) => Err(GloxError::runtime(message, index_span)),
+            }
+        }
+        object => Err(not_indexable(&object, object_span)),
+    }
+}
+
+pub fn set_index(
+    object: Value,
+    index: Value,
+    value: Value,
+    object_span: Span,
+    index_span: Span,
+) -> Result<(), GloxError> {
+    match object {
+        Value::List(list) => {
+            let i = element(&list.borrow(), &index, index_span)?;
+            list.borrow_mut()[i] = value;
+            Ok(())
+        }
+        Value::Map(map) => map
+            .borrow_mut()
+            .insert(index, value)
+            .map_err(|message| GloxError::runtime(message, index_span)),
+        object => Err(not_indexable(&object, object_span)),
+    }
+}
+
+fn not_indexable(object: &Value, span: Span) -> GloxError {
+    GloxError::runtime(
+        format!(
+            "Only lists and maps can be indexed, not {}.",
+            object.type_name()
+        ),
+        span,
+    )
+}
+
+// Negative indices count back from the end, so xs[-1] is the last element
+fn element(list: &[Value], index: &Value, span: Span) -> Result<usize, GloxError> {
+    let Value::Int(i) = index else {
+        return Err(GloxError::runtime(
+            format!("List indices must be ints, not {}.", index.type_name()),
+            span,
+        ));
+    };
+    list_index(*i, list.len()).ok_or_else(|| {
+        GloxError::runtime(
+            format!(
+                "Index {} is out of bounds for a list of length {}.",
+                i,
+                list.len()
+            ),
+            span,
+        )
+    })
+}
+
+// The operands of an arithmetic or comparison operator, after promotion
+enum Numbers {
+    Ints(i64, i64),
+    Floats(f64, f64),
+}
+
+// None if either operand isn't a number
+fn numbers(left: &Value, right: &Value) -> Option<Numbers> {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => Some(Numbers::Ints(*l, *r)),
+        (Value::Int(l), Value::Float(r)) => Some(Numbers::Floats(*l as f64, *r)),
+        (Value::Float(l), Value::Int(r)) => Some(Numbers::Floats(*l, *r as f64)),
+        (Value::Float(l), Value::Float(r)) => Some(Numbers::Floats(*l, *r)),
+        _ => None,
+    }
+}
+
+// Comparisons with NaN are always false. An int and a float are compared by
+// their exact values like == does, not promoted.
+fn compare(
+    left: &Value,
+    right: &Value,
+    operator: &str,
+    test: fn(Ordering) -> bool,
+    span: Span,
+) -> Result<Value, GloxError> {
+    let ordering = match (left, right) {
+        (Value::Int(l), Value::Float(r)) => order_int_float(*l, *r),
+        (Value::Float(l), Value::Int(r)) => order_int_float(*r, *l).map(Ordering::reverse),
+        _ => match numbers(left, right) {
+            Some(Numbers::Ints(l, r)) => Some(l.cmp(&r)),
+            Some(Numbers::Floats(l, r)) => l.partial_cmp(&r),
+            None => {
+                return Err(GloxError::runtime(
+                    format!("You can only do {} between two numbers big bro", operator),
+                    span,
+                ));
+            }
+        },
+    };
+    Ok(Value::Bool(ordering.is_some_and(test)))
+}
+
+// How an int orders against a float, None if the float is NaN. Converting the
+// int would round it above 2^53, so the int is compared with the float's floor,
+// which converts exactly once the float is known to be in range.
+fn order_int_float(int: i64, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        None
+    } else if float >= i64::MAX as f64 {
+        Some(Ordering::Less)
+    } else if float < i64::MIN as f64 {
+        Some(Ordering::Greater)
+    } else {
+        let floor = float.floor();
+        let fraction = if float > floor {
+            Ordering::Less
+        } else {
+            Ordering::Equal
+        };
+        Some(int.cmp(&(floor as i64)).then(fraction))
+    }
+}
+
+// Integer arithmetic is checked rather than wrapping around
+fn checked(result: Option<i64>, span: Span) -> Result<Value, GloxError> {
+    result.map(Value::Int).ok_or_else(|| {
+        GloxError::runtime("Integer overflow.", span)
+            .with_help("use a float if the result doesn't need to be exact")
+    })
+}
+
+fn division_by_zero(span: Span) -> GloxError {
+    GloxError::runtime("Division by zero.", span)
+}
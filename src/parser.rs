@@ -1,39 +1,390 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
 use crate::error::GloxError;
 
-use crate::expr::{Binary, Expr, Grouping, LiteralExpr, Unary};
-use crate::token::{Literal, Token, TokenType};
+use crate::expr::{
+    Assign, Binary, Call, Depth, Expr, Get, Grouping, Index, Interpolate, ListExpr, LiteralExpr,
+    Logical, MapExpr, Set, SetIndex, Super, This, Unary, Variable,
+};
+use crate::scanner::Scanner;
+use crate::stmt::{
+    BlockStmt, ClassStmt, ExpressionStmt, ForStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt,
+    Stmt, VarStmt, WhileStmt,
+};
+use crate::token::{AstToken, Literal, Span, Token, TokenType};
+
+// Matches the limit in clox, where the argument count has to fit in a byte
+const MAX_ARGUMENTS: usize = 255;
 
-// A Recursive Decent Parser
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
+// A Recursive Decent Parser. Tokens are pulled from the scanner as they're
+// needed rather than all being scanned up front.
+pub struct Parser<'a> {
+    tokens: Scanner<'a>,
+    // The token after previous, which hasn't been consumed yet
+    next: Token<'a>,
+    previous: Option<Token<'a>>,
+    // One copy of each lexeme the syntax tree keeps, shared between its tokens
+    lexemes: HashMap<&'a str, Rc<str>>,
+    // Errors that were reported without unwinding the parser, plus every
+    // error a declaration was abandoned for
+    errors: Vec<GloxError>,
+    // Bad input the scanner skipped. These are reported instead of any syntax
+    // errors, which are likely to be knock-on effects of them.
+    lexical_errors: Vec<GloxError>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a str) -> Parser<'a> {
+        let mut tokens = Scanner::new(source);
+        let mut lexical_errors: Vec<GloxError> = Vec::new();
+        let next: Token<'a> = pull(&mut tokens, &mut lexical_errors);
         Parser {
-            tokens: tokens,
-            current: 0,
+            tokens,
+            next,
+            previous: None,
+            lexemes: HashMap::new(),
+            errors: Vec::new(),
+            lexical_errors,
+        }
+    }
+
+    // A program is a list of statements terminated by EOF. Parsing carries on
+    // past syntax errors so that every error in the program is reported at once.
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<GloxError>> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        while !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        if !self.lexical_errors.is_empty() {
+            Err(std::mem::take(&mut self.lexical_errors))
+        } else if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    // Parses the tokens as a single expression like `1 + 2`, with nothing after it
+    pub fn parse_expression(&mut self) -> Result<Expr, Vec<GloxError>> {
+        let result = self.expression();
+        if !self.lexical_errors.is_empty() {
+            // Skip to the end so that every lexical error is reported
+            while !self.is_at_end() {
+                self.advance();
+            }
+            return Err(std::mem::take(&mut self.lexical_errors));
+        }
+        match result {
+            Ok(expr) if self.is_at_end() && self.errors.is_empty() => return Ok(expr),
+            Ok(_) if self.is_at_end() => {}
+            Ok(_) => {
+                let error = GloxError::parse("Expect end of expression.", self.peek_span());
+                self.errors.push(error);
+            }
+            Err(e) => self.errors.push(e),
+        }
+        Err(std::mem::take(&mut self.errors))
+    }
+
+    // Errors unwind to here, where the parser records them and skips ahead to
+    // the next statement so it can keep looking for more errors
+    fn declaration(&mut self) -> Option<Stmt> {
+        match self.declaration_inner() {
+            Ok(statement) => Some(statement),
+            Err(e) => {
+                self.errors.push(e);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn declaration_inner(&mut self) -> Result<Stmt, GloxError> {
+        if self.match_token_type(&[TokenType::Class]) {
+            return self.class_declaration();
+        } else if self.match_token_type(&[TokenType::Fun]) {
+            return Ok(Stmt::Function(self.function("function")?));
+        } else if self.match_token_type(&[TokenType::Var]) {
+            return self.var_declaration();
         }
+        self.statement()
     }
 
-    pub fn parse(&mut self) -> Result<Expr, GloxError> {
-        self.expression()
+    fn class_declaration(&mut self) -> Result<Stmt, GloxError> {
+        let name: AstToken = self.consume(TokenType::Identifier, "Expect class name.")?;
+        let mut superclass: Option<Variable> = None;
+        if self.match_token_type(&[TokenType::Less]) {
+            self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            superclass = Some(Variable {
+                name: self.previous(),
+                depth: Depth::default(),
+            });
+        }
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods: Vec<Rc<FunctionStmt>> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class(ClassStmt {
+            name,
+            superclass,
+            methods,
+        }))
+    }
+
+    // The kind is only used in error messages
+    fn function(&mut self, kind: &str) -> Result<Rc<FunctionStmt>, GloxError> {
+        let name: AstToken =
+            self.consume(TokenType::Identifier, &format!("Expect {kind} name."))?;
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {kind} name."),
+        )?;
+        let mut params: Vec<AstToken> = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    // The parser isn't confused, so report it without unwinding
+                    self.errors.push(GloxError::parse(
+                        format!("Can't have more than {MAX_ARGUMENTS} parameters."),
+                        self.peek_span(),
+                    ));
+                }
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+                if !self.match_token_type(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {kind} body."),
+        )?;
+        let body: Vec<Stmt> = self.block()?;
+        Ok(Rc::new(FunctionStmt { name, params, body }))
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt, GloxError> {
+        let name: AstToken = self.consume(TokenType::Identifier, "Expect variable name.")?;
+        let mut initializer: Option<Expr> = None;
+        if self.match_token_type(&[TokenType::Equal]) {
+            initializer = Some(self.expression()?);
+        }
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Var(VarStmt { name, initializer }))
+    }
+
+    fn statement(&mut self) -> Result<Stmt, GloxError> {
+        if self.match_token_type(&[TokenType::For]) {
+            return self.for_statement();
+        } else if self.match_token_type(&[TokenType::If]) {
+            return self.if_statement();
+        } else if self.match_token_type(&[TokenType::Print]) {
+            return self.print_statement();
+        } else if self.match_token_type(&[TokenType::Return]) {
+            return self.return_statement();
+        } else if self.match_token_type(&[TokenType::While]) {
+            return self.while_statement();
+        } else if self.match_token_type(&[TokenType::LeftBrace]) {
+            return Ok(Stmt::Block(BlockStmt {
+                statements: self.block()?,
+            }));
+        }
+        self.expression_statement()
+    }
+
+    // `for (init; cond; incr) body`, kept as a ForStmt with each clause optional
+    fn for_statement(&mut self) -> Result<Stmt, GloxError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+        let initializer: Option<Box<Stmt>> = if self.match_token_type(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_token_type(&[TokenType::Var]) {
+            Some(Box::new(self.var_declaration()?))
+        } else {
+            Some(Box::new(self.expression_statement()?))
+        };
+
+        let mut condition: Option<Expr> = None;
+        if !self.check(TokenType::Semicolon) {
+            condition = Some(self.expression()?);
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let mut increment: Option<Expr> = None;
+        if !self.check(TokenType::RightParen) {
+            increment = Some(self.expression()?);
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let body: Stmt = self.statement()?;
+        Ok(Stmt::For(ForStmt {
+            initializer,
+            condition,
+            increment,
+            body: Box::new(body),
+        }))
+    }
+
+    // The else is bound to the nearest if, which resolves the dangling else ambiguity
+    fn if_statement(&mut self) -> Result<Stmt, GloxError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition: Expr = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch: Stmt = self.statement()?;
+        let mut else_branch: Option<Box<Stmt>> = None;
+        if self.match_token_type(&[TokenType::Else]) {
+            else_branch = Some(Box::new(self.statement()?));
+        }
+        Ok(Stmt::If(IfStmt {
+            condition,
+            then_branch: Box::new(then_branch),
+            else_branch,
+        }))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, GloxError> {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition: Expr = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body: Stmt = self.statement()?;
+        Ok(Stmt::While(WhileStmt {
+            condition,
+            body: Box::new(body),
+        }))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, GloxError> {
+        let value: Expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(PrintStmt { expression: value }))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, GloxError> {
+        let keyword: AstToken = self.previous();
+        let mut value: Option<Expr> = None;
+        if !self.check(TokenType::Semicolon) {
+            value = Some(self.expression()?);
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(ReturnStmt { keyword, value }))
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt, GloxError> {
+        let expr: Expr = self.expression()?;
+        // Most likely `{"a": 1}` at the start of a statement, which is a block
+        if self.check(TokenType::Colon) {
+            return Err(GloxError::parse("Expect ';' after expression.", self.peek_span())
+                .with_help("a '{' that starts a statement begins a block, put a map literal in parentheses"));
+        }
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression(ExpressionStmt { expression: expr }))
+    }
+
+    // Parses the statements of a block, the opening brace has already been consumed
+    fn block(&mut self) -> Result<Vec<Stmt>, GloxError> {
+        let mut statements: Vec<Stmt> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
     }
 
     fn expression(&mut self) -> Result<Expr, GloxError> {
-        self.equality()
+        self.assignment()
+    }
+
+    // Assignment is right associative, so we parse the left hand side as a normal
+    // expression and only afterwards check that it is a valid assignment target
+    fn assignment(&mut self) -> Result<Expr, GloxError> {
+        let expr: Expr = self.or()?;
+        if self.match_token_type(&[TokenType::Equal]) {
+            let equals: AstToken = self.previous();
+            let value: Expr = self.assignment()?;
+            match expr {
+                Expr::Variable(variable) => {
+                    return Ok(Expr::Assign(Assign {
+                        name: variable.name,
+                        value: Box::new(value),
+                        depth: Depth::default(),
+                    }));
+                }
+                Expr::Get(get) => {
+                    return Ok(Expr::Set(Set {
+                        object: get.object,
+                        name: get.name,
+                        value: Box::new(value),
+                    }));
+                }
+                Expr::Index(index) => {
+                    return Ok(Expr::SetIndex(SetIndex {
+                        object: index.object,
+                        index: index.index,
+                        bracket: index.bracket,
+                        value: Box::new(value),
+                    }));
+                }
+                _ => {}
+            }
+            self.errors.push(
+                GloxError::parse("Invalid assignment target.", equals.span).with_help(
+                    "only variables, properties and index expressions can be assigned to",
+                ),
+            );
+            return Ok(value);
+        }
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, GloxError> {
+        let mut expr: Expr = self.and()?;
+        while self.match_token_type(&[TokenType::Or]) {
+            let operator: AstToken = self.previous();
+            let right: Expr = self.and()?;
+            expr = Expr::Logical(Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, GloxError> {
+        let mut expr: Expr = self.equality()?;
+        while self.match_token_type(&[TokenType::And]) {
+            let operator: AstToken = self.previous();
+            let right: Expr = self.equality()?;
+            expr = Expr::Logical(Logical {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
     }
 
     fn equality(&mut self) -> Result<Expr, GloxError> {
         let mut expr = self.comparison()?;
         let equality_types: [TokenType; 2] = [TokenType::BangEqual, TokenType::EqualEqual];
         while self.match_token_type(&equality_types) {
-            let operator: Token = self.previous();
+            let operator: AstToken = self.previous();
             let right: Expr = self.comparison()?;
             expr = Expr::Binary(Binary {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right: Box::new(right),
             });
         }
@@ -49,11 +400,11 @@ impl Parser {
             TokenType::LessEqual,
         ];
         while self.match_token_type(&comparison_types) {
-            let operator: Token = self.previous();
+            let operator: AstToken = self.previous();
             let right: Expr = self.term()?;
             expr = Expr::Binary(Binary {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right: Box::new(right),
             });
         }
@@ -64,11 +415,11 @@ impl Parser {
         let mut expr: Expr = self.factor()?;
         let term_types: [TokenType; 2] = [TokenType::Minus, TokenType::Plus];
         while self.match_token_type(&term_types) {
-            let operator: Token = self.previous();
+            let operator: AstToken = self.previous();
             let right: Expr = self.factor()?;
             expr = Expr::Binary(Binary {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right: Box::new(right),
             });
         }
@@ -77,13 +428,18 @@ impl Parser {
 
     fn factor(&mut self) -> Result<Expr, GloxError> {
         let mut expr: Expr = self.unary()?;
-        let factor_types: [TokenType; 2] = [TokenType::Slash, TokenType::Star];
+        let factor_types: [TokenType; 4] = [
+            TokenType::Slash,
+            TokenType::Star,
+            TokenType::Percent,
+            TokenType::TildeSlash,
+        ];
         while self.match_token_type(&factor_types) {
-            let operator: Token = self.previous();
-            let right: Expr = self.factor()?;
+            let operator: AstToken = self.previous();
+            let right: Expr = self.unary()?;
             expr = Expr::Binary(Binary {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right: Box::new(right),
             });
         }
@@ -93,85 +449,289 @@ impl Parser {
     fn unary(&mut self) -> Result<Expr, GloxError> {
         let unary_types: [TokenType; 2] = [TokenType::Bang, TokenType::Minus];
         if self.match_token_type(&unary_types) {
-            let operator: Token = self.previous();
+            let operator: AstToken = self.previous();
             let right: Expr = self.unary()?;
             return Ok(Expr::Unary(Unary {
-                operator: operator,
+                operator,
                 right: Box::new(right),
             }));
         }
-        self.primary()
+        self.call()
+    }
+
+    // Calls and property accesses are left associative so `a.b(1).c` works on
+    // the result of each previous step
+    fn call(&mut self) -> Result<Expr, GloxError> {
+        let mut expr: Expr = self.primary()?;
+        loop {
+            if self.match_token_type(&[TokenType::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token_type(&[TokenType::Dot]) {
+                let name: AstToken =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Expr::Get(Get {
+                    object: Box::new(expr),
+                    name,
+                });
+            } else if self.match_token_type(&[TokenType::LeftBracket]) {
+                let index: Expr = self.expression()?;
+                let bracket: AstToken =
+                    self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::Index(Index {
+                    object: Box::new(expr),
+                    index: Box::new(index),
+                    bracket,
+                });
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, GloxError> {
+        let mut arguments: Vec<Expr> = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    self.errors.push(GloxError::parse(
+                        format!("Can't have more than {MAX_ARGUMENTS} arguments."),
+                        self.peek_span(),
+                    ));
+                }
+                arguments.push(self.expression()?);
+                if !self.match_token_type(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let paren: AstToken = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+        Ok(Expr::Call(Call {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        }))
+    }
+
+    // "a ${b} c" is scanned as Interpolation("a "), the tokens of b and then
+    // String(" c"), and parsed into an Interpolate with the parts "a ", b and " c"
+    fn interpolation(&mut self) -> Result<Expr, GloxError> {
+        let start: Span = self.previous_span();
+        let mut parts: Vec<Expr> = vec![self.literal()];
+        loop {
+            // The rest of the string starts at the closing brace
+            if matches!(self.peek(), TokenType::String | TokenType::Interpolation)
+                && self.peek_lexeme().starts_with('}')
+            {
+                return Err(GloxError::parse(
+                    "Expect expression inside '${}'.",
+                    self.peek_span(),
+                ));
+            }
+            parts.push(self.expression()?);
+            if !self.match_token_type(&[TokenType::Interpolation]) {
+                self.consume(
+                    TokenType::String,
+                    "Expect '}' after interpolated expression.",
+                )?;
+                parts.push(self.literal());
+                return Ok(Expr::Interpolate(Interpolate {
+                    parts,
+                    span: start.to(self.previous_span()),
+                }));
+            }
+            parts.push(self.literal());
+        }
+    }
+
+    // A string, number or part of an interpolated string
+    fn literal(&mut self) -> Expr {
+        let token: &Token<'a> = self.previous.as_ref().unwrap();
+        Expr::Literal(LiteralExpr {
+            value: token.literal.clone(),
+            span: token.span,
+        })
+    }
+
+    // The elements of a list literal, a trailing comma is allowed
+    fn list(&mut self) -> Result<Expr, GloxError> {
+        let left: Span = self.previous_span();
+        let mut elements: Vec<Expr> = Vec::new();
+        while !self.check(TokenType::RightBracket) {
+            elements.push(self.expression()?);
+            if !self.match_token_type(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        let right: Span =
+            self.consume_span(TokenType::RightBracket, "Expect ']' after list elements.")?;
+        Ok(Expr::List(ListExpr {
+            elements,
+            span: left.to(right),
+        }))
+    }
+
+    // The entries of a map literal, `{"a": 1, "b": 2}`. Keys are expressions too,
+    // and like lists a trailing comma is allowed.
+    fn map(&mut self) -> Result<Expr, GloxError> {
+        let left: Span = self.previous_span();
+        let mut entries: Vec<(Expr, Expr)> = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            let key: Expr = self.expression()?;
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            let value: Expr = self.expression()?;
+            entries.push((key, value));
+            if !self.match_token_type(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        let right: Span =
+            self.consume_span(TokenType::RightBrace, "Expect '}' after map entries.")?;
+        Ok(Expr::Map(MapExpr {
+            entries,
+            span: left.to(right),
+        }))
     }
 
     fn primary(&mut self) -> Result<Expr, GloxError> {
         if self.match_token_type(&[TokenType::False]) {
             return Ok(Expr::Literal(LiteralExpr {
                 value: Literal::Bool(false),
+                span: self.previous_span(),
             }));
         } else if self.match_token_type(&[TokenType::True]) {
             return Ok(Expr::Literal(LiteralExpr {
                 value: Literal::Bool(true),
+                span: self.previous_span(),
             }));
         } else if self.match_token_type(&[TokenType::Nil]) {
             return Ok(Expr::Literal(LiteralExpr {
                 value: Literal::Nil,
+                span: self.previous_span(),
             }));
         } else if self.match_token_type(&[TokenType::String, TokenType::Number]) {
-            return Ok(Expr::Literal(LiteralExpr {
-                value: self.previous().literal,
+            return Ok(self.literal());
+        } else if self.match_token_type(&[TokenType::Interpolation]) {
+            return self.interpolation();
+        } else if self.match_token_type(&[TokenType::Super]) {
+            let keyword: AstToken = self.previous();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method: AstToken =
+                self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(Expr::Super(Super {
+                keyword,
+                method,
+                depth: Depth::default(),
+            }));
+        } else if self.match_token_type(&[TokenType::This]) {
+            return Ok(Expr::This(This {
+                keyword: self.previous(),
+                depth: Depth::default(),
             }));
+        } else if self.match_token_type(&[TokenType::Identifier]) {
+            return Ok(Expr::Variable(Variable {
+                name: self.previous(),
+                depth: Depth::default(),
+            }));
+        } else if self.match_token_type(&[TokenType::LeftBracket]) {
+            return self.list();
+        } else if self.match_token_type(&[TokenType::LeftBrace]) {
+            return self.map();
         } else if self.match_token_type(&[TokenType::LeftParen]) {
+            let left: Span = self.previous_span();
             let expr: Expr = self.expression()?;
-            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            let right: Span =
+                self.consume_span(TokenType::RightParen, "Expect ')' after expression.")?;
             return Ok(Expr::Grouping(Grouping {
                 expression: Box::new(expr),
+                span: left.to(right),
             }));
         }
 
-        Err(GloxError::UnexpectedToken(
-            self.peek_lexeme(),
-            self.current_line().try_into().unwrap(),
+        let found: String = match self.peek() {
+            TokenType::EOF => "end of file".to_string(),
+            _ => format!("'{}'", self.peek_lexeme()),
+        };
+        Err(GloxError::parse(
+            format!("Expect expression, found {}.", found),
+            self.peek_span(),
         ))
     }
 
     fn peek(&mut self) -> TokenType {
-        return self.tokens.get(self.current).unwrap().token_type;
+        self.next.token_type
+    }
+
+    fn peek_lexeme(&mut self) -> &'a str {
+        self.next.lexeme
     }
 
-    fn peek_lexeme(&mut self) -> String {
-        return self.tokens.get(self.current).unwrap().lexeme.clone();
+    fn peek_span(&self) -> Span {
+        self.next.span
     }
 
-    fn current_line(&self) -> usize {
-        if self.current < self.tokens.len() {
-            self.tokens[self.current].line as usize
-        } else if !self.tokens.is_empty() {
-            self.tokens[self.tokens.len() - 1].line as usize
+    // Where to point when a token is missing. If the next token is on a later line
+    // (or there isn't one) it's clearer to point just past the previous token.
+    fn missing_token_span(&mut self) -> Span {
+        let next: Span = self.peek_span();
+        let Some(previous) = &self.previous else {
+            return next;
+        };
+        // Columns count characters, and a string can run over several lines
+        let span: Span = previous.span;
+        let (line, column) = match previous.lexeme.rsplit_once('\n') {
+            Some((before, last)) => (
+                span.line + before.matches('\n').count() + 1,
+                last.chars().count() + 1,
+            ),
+            None => (span.line, span.column + previous.lexeme.chars().count()),
+        };
+        if self.is_at_end() || next.line > line {
+            Span {
+                start: span.end,
+                end: span.end,
+                line,
+                column,
+            }
         } else {
-            1
+            next
         }
     }
 
-    fn previous(&mut self) -> Token {
-        return self.tokens.get(self.current - 1).unwrap().clone();
+    // The token just consumed, to keep in the syntax tree
+    fn previous(&mut self) -> AstToken {
+        let token: &Token<'a> = self.previous.as_ref().unwrap();
+        let lexeme = self
+            .lexemes
+            .entry(token.lexeme)
+            .or_insert_with(|| Rc::from(token.lexeme));
+        AstToken {
+            token_type: token.token_type,
+            lexeme: Rc::clone(lexeme),
+            span: token.span,
+        }
+    }
+
+    fn previous_span(&self) -> Span {
+        self.previous.as_ref().unwrap().span
     }
 
     fn is_at_end(&mut self) -> bool {
-        return self.peek() == TokenType::EOF;
+        self.peek() == TokenType::EOF
     }
 
-    fn advance(&mut self) -> Token {
+    fn advance(&mut self) {
         if !self.is_at_end() {
-            self.current += 1
+            let next: Token<'a> = pull(&mut self.tokens, &mut self.lexical_errors);
+            self.previous = Some(std::mem::replace(&mut self.next, next));
         }
-        return self.previous();
     }
 
     fn check(&mut self, typ: TokenType) -> bool {
         if self.is_at_end() {
             return false;
         }
-        return self.peek() == typ;
+        self.peek() == typ
     }
 
     fn match_token_type(&mut self, token_types: &[TokenType]) -> bool {
@@ -181,39 +741,59 @@ impl Parser {
                 return true;
             }
         }
-        return false;
+        false
+    }
+
+    fn consume(&mut self, typ: TokenType, message: &str) -> Result<AstToken, GloxError> {
+        self.consume_span(typ, message)?;
+        Ok(self.previous())
     }
 
-    fn consume(&mut self, typ: TokenType, message: &str) -> Result<Token, GloxError> {
+    // Like consume, for tokens that only matter for where they are
+    fn consume_span(&mut self, typ: TokenType, message: &str) -> Result<Span, GloxError> {
         if self.check(typ) {
-            Ok(self.advance())
-        } else {
-            Err(GloxError::UnexpectedToken(
-                message.to_string(),
-                self.current_line().try_into().unwrap(),
-            ))
-        }
-    }
-
-    // fn synchronize(&mut self) {
-    //     self.advance();
-    //     while !self.is_at_end() {
-    //         if self.previous().token_type == TokenType::Semicolon {
-    //             return;
-    //         }
-    //     }
-    //     match self.peek() {
-    //         TokenType::Class
-    //         | TokenType::For
-    //         | TokenType::If
-    //         | TokenType::Print
-    //         | TokenType::Return
-    //         | TokenType::Var
-    //         | TokenType::While
-    //         | TokenType::Fun => return,
-    //         _ => {
-    //             self.advance();
-    //         }
-    //     }
-    // }
+            self.advance();
+            return Ok(self.previous_span());
+        }
+        let error = GloxError::parse(message, self.missing_token_span());
+        if typ == TokenType::Semicolon {
+            return Err(error.with_help("add a ';' to end the statement"));
+        }
+        Err(error)
+    }
+
+    // Discards tokens until we're probably at the start of the next statement,
+    // either just past a semicolon or at a keyword that begins a statement
+    fn synchronize(&mut self) {
+        self.advance();
+        while !self.is_at_end() {
+            if self.previous.as_ref().unwrap().token_type == TokenType::Semicolon {
+                return;
+            }
+            match self.peek() {
+                TokenType::Class
+                | TokenType::For
+                | TokenType::If
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Var
+                | TokenType::While
+                | TokenType::Fun => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+}
+
+// The next token from the scanner, setting aside any errors that come before it
+fn pull<'a>(tokens: &mut Scanner<'a>, errors: &mut Vec<GloxError>) -> Token<'a> {
+    for result in tokens {
+        match result {
+            Ok(token) => return token,
+            Err(e) => errors.push(e),
+        }
+    }
+    unreachable!("the parser doesn't read past EOF")
 }
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+use std::time::Instant;
+use std::{env, fs};
+
+use glox::{Backend, Glox, GloxError, TokenType, Value};
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+
+use crate::{print_tokens, report};
+
+// Lives in the home directory so history is shared between sessions
+const HISTORY_FILE: &str = ".glox_history";
+
+// What errors in an entry are reported against
+const FILENAME: &str = "<prompt>";
+
+// Commands the prompt understands on top of Lox itself, for poking at the session
+const HELP: &str = "\
+:tokens <code>  show the tokens the scanner produces for the code
+:ast <code>     show the tree the parser produces for the code
+:disasm <code>  show the bytecode the compiler produces for the code
+:env            list the global variables and their values
+:load <file>    run a file in this session
+:reset          forget everything defined so far
+:time <code>    run the code and show how long it took
+:help           show this message";
+
+// An interactive session. Every entry runs against the same Glox, so
+// definitions carry over from one entry to the next.
+pub struct Repl {
+    glox: Glox,
+}
+
+impl Repl {
+    pub fn new(backend: Backend) -> Repl {
+        Repl {
+            glox: Glox::with_backend(backend),
+        }
+    }
+
+    // Reads entries until EOF (Ctrl-D)
+    pub fn run(&mut self) {
+        let mut editor = DefaultEditor::new().expect("failed to start the line editor");
+        let history = history_path();
+        if let Some(path) = &history {
+            // There's no history yet on the first run
+            let _ = editor.load_history(path);
+        }
+
+        let mut entry = String::new();
+        loop {
+            let prompt = if entry.is_empty() { "> " } else { "... " };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    if entry.is_empty() && line.starts_with(':') {
+                        let _ = editor.add_history_entry(line.as_str());
+                        self.run_command(&line);
+                        continue;
+                    }
+                    entry.push_str(&line);
+                    entry.push('\n');
+                    if is_incomplete(&entry) {
+                        continue;
+                    }
+                    let entry = std::mem::take(&mut entry);
+                    if entry.trim().is_empty() {
+                        continue;
+                    }
+                    let _ = editor.add_history_entry(entry.trim_end());
+                    self.run_entry(&entry);
+                }
+                // Ctrl-C throws away whatever has been typed so far
+                Err(ReadlineError::Interrupted) => entry.clear(),
+                Err(ReadlineError::Eof) => break,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    break;
+                }
+            }
+        }
+
+        if let Some(path) = &history
+            && let Err(e) = editor.save_history(path)
+        {
+            eprintln!("Error: failed to save history: {}", e);
+        }
+    }
+
+    // A bare expression such as `1 + 2` is evaluated and its value printed,
+    // anything else is run like a line of a script
+    fn run_entry(&mut self, entry: &str) {
+        match self.glox.eval(entry) {
+            Ok(value) => println!("{}", value),
+            Err(errors)
+                if errors
+                    .iter()
+                    .all(|e| matches!(e, GloxError::UnexpectedToken(_))) =>
+            {
+                if let Err(errors) = self.glox.run(entry) {
+                    report(&errors, entry, FILENAME);
+                }
+            }
+            Err(errors) => report(&errors, entry, FILENAME),
+        }
+    }
+
+    fn run_command(&mut self, command: &str) {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        let argument = argument.trim();
+        // Errors in the argument are reported against it rather than the whole line
+        match name {
+            ":tokens" => match Glox::tokenize(argument) {
+                Ok(tokens) => print_tokens(&tokens),
+                Err(errors) => report(&errors, argument, FILENAME),
+            },
+            ":ast" => match Glox::syntax_tree(argument) {
+                Ok(tree) => println!("{}", tree),
+                Err(errors) => report(&errors, argument, FILENAME),
+            },
+            ":disasm" => match Glox::disassemble(argument) {
+                Ok(listing) => print!("{}", listing),
+                Err(errors) => report(&errors, argument, FILENAME),
+            },
+            ":env" => {
+                // The standard library would drown out everything else
+                for (name, value) in self.glox.globals() {
+                    if !matches!(value, Value::Native(_)) {
+                        println!("{} = {}", name, value);
+                    }
+                }
+            }
+            ":load" => self.load(argument),
+            ":reset" => {
+                self.glox = Glox::with_backend(self.glox.backend());
+                println!("Session reset.");
+            }
+            ":time" => {
+                let start = Instant::now();
+                self.run_entry(argument);
+                println!("Took {:?}", start.elapsed());
+            }
+            ":help" => println!("{}", HELP),
+            _ => eprintln!("Unknown command '{}', try :help", name),
+        }
+    }
+
+    fn load(&mut self, filepath: &str) {
+        match fs::read_to_string(filepath) {
+            Ok(code) => {
+                if let Err(errors) = self.glox.run(&code) {
+                    report(&errors, &code, filepath);
+                }
+            }
+            Err(e) => eprintln!("Error: failed to read {}: {}", filepath, e),
+        }
+    }
+}
+
+// An entry carries on to the next line while it has unclosed brackets or an
+// unterminated string
+fn is_incomplete(source: &str) -> bool {
+    match Glox::tokenize(source) {
+        Ok(tokens) => {
+            let mut depth = 0;
+            for token in &tokens {
+                match token.token_type {
+                    TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => {
+                        depth += 1
+                    }
+                    TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                        depth -= 1
+                    }
+                    _ => {}
+                }
+            }
+            depth > 0
+        }
+        Err(errors) => errors
+            .iter()
+            .any(|e| matches!(e, GloxError::UnterminatedString(_))),
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(HISTORY_FILE))
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::GloxError;
+use crate::expr::{
+    Assign, Binary, Call, Depth, Expr, Get, Grouping, Index, Interpolate, ListExpr, LiteralExpr,
+    Logical, MapExpr, Set, SetIndex, Super, This, Unary, Variable, Visitor,
+};
+use crate::stmt::{
+    BlockStmt, ClassStmt, ExpressionStmt, ForStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt,
+    Stmt, StmtVisitor, VarStmt, WhileStmt,
+};
+use crate::token::AstToken;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+// A static pass between the parser and the interpreter. It walks the tree once,
+// recording how many scopes away each local variable was declared so the interpreter
+// can find exactly that binding later, and reports errors that don't need a runtime.
+pub struct Resolver {
+    // Each scope maps a name to whether its initializer has finished resolving.
+    // Globals aren't tracked, a name not found in any scope is assumed to be global.
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<GloxError>,
+}
+
+impl Visitor<()> for Resolver {
+    fn visit_literal(&mut self, _expr: &LiteralExpr) {}
+
+    fn visit_grouping(&mut self, expr: &Grouping) {
+        self.resolve_expr(&expr.expression);
+    }
+
+    fn visit_unary(&mut self, expr: &Unary) {
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_binary(&mut self, expr: &Binary) {
+        self.resolve_expr(&expr.left);
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_variable(&mut self, expr: &Variable) {
+        if let Some(scope) = self.scopes.last()
+            && scope.get(&*expr.name.lexeme) == Some(&false)
+        {
+            self.errors.push(
+                GloxError::resolution(
+                    "Can't read local variable in its own initializer.",
+                    expr.name.span,
+                )
+                .with_help("the new variable shadows any outer variable with the same name"),
+            );
+        }
+        self.resolve_local(&expr.depth, &expr.name);
+    }
+
+    fn visit_assign(&mut self, expr: &Assign) {
+        self.resolve_expr(&expr.value);
+        self.resolve_local(&expr.depth, &expr.name);
+    }
+
+    fn visit_logical(&mut self, expr: &Logical) {
+        self.resolve_expr(&expr.left);
+        self.resolve_expr(&expr.right);
+    }
+
+    fn visit_call(&mut self, expr: &Call) {
+        self.resolve_expr(&expr.callee);
+        for argument in &expr.arguments {
+            self.resolve_expr(argument);
+        }
+    }
+
+    // Properties are looked up dynamically, so only the object is resolved
+    fn visit_get(&mut self, expr: &Get) {
+        self.resolve_expr(&expr.object);
+    }
+
+    fn visit_set(&mut self, expr: &Set) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+    }
+
+    fn visit_list(&mut self, expr: &ListExpr) {
+        for element in &expr.elements {
+            self.resolve_expr(element);
+        }
+    }
+
+    fn visit_map(&mut self, expr: &MapExpr) {
+        for (key, value) in &expr.entries {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+    }
+
+    fn visit_index(&mut self, expr: &Index) {
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_set_index(&mut self, expr: &SetIndex) {
+        self.resolve_expr(&expr.value);
+        self.resolve_expr(&expr.object);
+        self.resolve_expr(&expr.index);
+    }
+
+    fn visit_interpolate(&mut self, expr: &Interpolate) {
+        for part in &expr.parts {
+            self.resolve_expr(part);
+        }
+    }
+
+    fn visit_this(&mut self, expr: &This) {
+        if self.current_class == ClassType::None {
+            self.error(&expr.keyword, "Can't use 'this' outside of a class.");
+            return;
+        }
+        self.resolve_local(&expr.depth, &expr.keyword);
+    }
+
+    fn visit_super(&mut self, expr: &Super) {
+        match self.current_class {
+            ClassType::None => {
+                self.error(&expr.keyword, "Can't use 'super' outside of a class.");
+            }
+            ClassType::Class => {
+                self.error(
+                    &expr.keyword,
+                    "Can't use 'super' in a class with no superclass.",
+                );
+            }
+            ClassType::Subclass => self.resolve_local(&expr.depth, &expr.keyword),
+        }
+    }
+}
+
+impl StmtVisitor<()> for Resolver {
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) {
+        self.resolve_expr(&stmt.expression);
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) {
+        self.resolve_expr(&stmt.expression);
+    }
+
+    // Declaring and defining are split so the initializer can't see the variable
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) {
+        self.declare(&stmt.name);
+        if let Some(initializer) = &stmt.initializer {
+            self.resolve_expr(initializer);
+        }
+        self.define(&stmt.name);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) {
+        self.begin_scope();
+        self.resolve(&stmt.statements);
+        self.end_scope();
+    }
+
+    // Unlike the interpreter, both branches are always resolved
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) {
+        self.resolve_expr(&stmt.condition);
+        self.resolve_stmt(&stmt.then_branch);
+        if let Some(else_branch) = &stmt.else_branch {
+            self.resolve_stmt(else_branch);
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) {
+        self.resolve_expr(&stmt.condition);
+        self.resolve_stmt(&stmt.body);
+    }
+
+    // Mirrors the scope the interpreter creates around the whole loop
+    fn visit_for_stmt(&mut self, stmt: &ForStmt) {
+        self.begin_scope();
+        if let Some(initializer) = &stmt.initializer {
+            self.resolve_stmt(initializer);
+        }
+        if let Some(condition) = &stmt.condition {
+            self.resolve_expr(condition);
+        }
+        if let Some(increment) = &stmt.increment {
+            self.resolve_expr(increment);
+        }
+        self.resolve_stmt(&stmt.body);
+        self.end_scope();
+    }
+
+    // The name is defined before the body is resolved so functions can recurse
+    fn visit_function_stmt(&mut self, stmt: &Rc<FunctionStmt>) {
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve_function(stmt, FunctionType::Function);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) {
+        if self.current_function == FunctionType::None {
+            self.errors.push(
+                GloxError::resolution("Can't return from top-level code.", stmt.keyword.span)
+                    .with_help("'return' can only be used inside a function or method"),
+            );
+        }
+        if let Some(value) = &stmt.value {
+            if self.current_function == FunctionType::Initializer {
+                self.error(&stmt.keyword, "Can't return a value from an initializer.");
+            }
+            self.resolve_expr(value);
+        }
+    }
+
+    // Mirrors the environments the interpreter creates for a class: one holding
+    // `super` for subclasses, and one holding `this` around every method
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+
+        if let Some(superclass) = &stmt.superclass {
+            if superclass.name.lexeme == stmt.name.lexeme {
+                self.error(&superclass.name, "A class can't inherit from itself.");
+            }
+            self.current_class = ClassType::Subclass;
+            self.visit_variable(superclass);
+            self.begin_scope();
+            self.scopes
+                .last_mut()
+                .unwrap()
+                .insert("super".to_string(), true);
+        }
+
+        self.begin_scope();
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert("this".to_string(), true);
+        for method in &stmt.methods {
+            let function_type = if &*method.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+        self.end_scope();
+
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: Vec::new(),
+        }
+    }
+
+    // Resolves a whole program, returning every error found rather than just the first
+    pub fn resolve_program(mut self, statements: &[Stmt]) -> Result<(), Vec<GloxError>> {
+        self.resolve(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    pub fn resolve_expression(mut self, expr: &Expr) -> Result<(), Vec<GloxError>> {
+        self.resolve_expr(expr);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    fn resolve(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        stmt.accept(self)
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        expr.accept(self)
+    }
+
+    fn resolve_function(&mut self, function: &FunctionStmt, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+        self.begin_scope();
+        for param in &function.params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+        self.current_function = enclosing_function;
+    }
+
+    // Walks outwards from the innermost scope and records how far away the
+    // variable was found. Leaving the depth unset means it's a global.
+    fn resolve_local(&mut self, depth: &Depth, name: &AstToken) {
+        for (i, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&*name.lexeme) {
+                depth.set(Some(i));
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &AstToken) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&*name.lexeme) {
+            self.error(name, "Already a variable with this name in this scope.");
+            return;
+        }
+        scope.insert(name.lexeme.to_string(), false);
+    }
+
+    fn define(&mut self, name: &AstToken) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.to_string(), true);
+        }
+    }
+
+    fn error(&mut self, token: &AstToken, message: &str) {
+        self.errors.push(GloxError::resolution(message, token.span));
+    }
+}
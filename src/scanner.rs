@@ -1,46 +1,131 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::VecDeque;
 
-use crate::token::{self, Literal, Token, TokenType};
-pub struct Scanner {
-    source: String,
-    chars: Vec<char>,
-    tokens: Vec<Token>,
+use crate::error::GloxError;
+use crate::token::{self, Literal, Span, Token, TokenType};
+
+// Turns source into tokens on demand, as an iterator the parser pulls from. Bad
+// input comes out as errors in among the tokens and scanning carries on past it,
+// so every lexical error is found in one go. The last token is always EOF.
+pub struct Scanner<'a> {
+    source: &'a str,
+    // Byte offsets into the source. Everything is sliced and spanned by byte, and
+    // only ever advanced a whole character at a time.
     start: usize,
     current: usize,
     line: usize,
-    keywords: HashMap<String, TokenType>,
+    // Of the current character, counted in characters rather than bytes
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    // Scanning a token can produce more than one result, e.g. a string with a
+    // bad escape in it, so they queue up here until they're asked for
+    pending: VecDeque<Result<Token<'a>, GloxError>>,
+    // Strings with a `${` that hasn't been closed yet, innermost last
+    open_strings: Vec<OpenString>,
+    // Where each comment is, as the tokens don't include them
+    comments: Vec<Span>,
+    done: bool,
+}
+
+// How a string is delimited, which decides how its contents are read
+#[derive(Clone, Copy)]
+struct Quotes {
+    // r"..." has no escapes or interpolation, backslashes are kept as written
+    raw: bool,
+    // """...""" can contain quotes
+    triple: bool,
+}
+
+// A string that's scanning the expression inside a `${...}`. The string carries
+// on at the `}` that brings the brace depth back to zero.
+struct OpenString {
+    quotes: Quotes,
+    // The opening quote, which unterminated string errors point at
+    opening: Span,
+    depth: usize,
 }
 
-impl Scanner {
-    pub fn new(source: String) -> Scanner {
-        let tokens: Vec<Token> = Vec::<Token>::new();
-        let chars: Vec<char> = source.chars().collect();
-        let keywords: HashMap<String, TokenType> = token::get_keywords();
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Result<Token<'a>, GloxError>;
+
+    fn next(&mut self) -> Option<Result<Token<'a>, GloxError>> {
+        while self.pending.is_empty() && !self.done {
+            if self.is_at_end() {
+                self.finish();
+            } else {
+                self.start = self.current;
+                self.start_line = self.line;
+                self.start_column = self.column;
+                self.scan_token();
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(source: &'a str) -> Scanner<'a> {
+        // A byte order mark isn't part of the program, but offsets still count it
+        // so that spans line up with the source as given
+        let current = if source.starts_with('\u{feff}') {
+            '\u{feff}'.len_utf8()
+        } else {
+            0
+        };
         Scanner {
-            source: source,
-            chars: chars,
-            tokens: tokens,
-            start: 0,
-            current: 0,
+            source,
+            start: current,
+            current,
             line: 1,
-            keywords: keywords,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            pending: VecDeque::new(),
+            open_strings: Vec::new(),
+            comments: Vec::new(),
+            done: false,
+        }
+    }
+
+    // The comments scanned so far
+    pub fn comments(&self) -> &[Span] {
+        &self.comments
+    }
+
+    // Scans the whole source at once, for when every token is needed up front
+    pub fn scan_tokens(self) -> Result<Vec<Token<'a>>, Vec<GloxError>> {
+        let mut tokens: Vec<Token<'a>> = Vec::new();
+        let mut errors: Vec<GloxError> = Vec::new();
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(e) => errors.push(e),
+            }
+        }
+        if errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(errors)
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+    fn finish(&mut self) {
+        // Only the outermost string is reported, it's the one that needs closing
+        if let Some(open) = self.open_strings.first() {
+            let error = self.unterminated_string(open.opening, open.quotes);
+            self.error(error);
+            self.open_strings.clear();
         }
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+        self.add_token(TokenType::EOF);
+        self.done = true;
+    }
 
-        let final_token = Token {
-            token_type: TokenType::EOF,
-            lexeme: "".to_string(),
-            literal: Literal::Nil,
-            line: self.line,
-        };
-        self.tokens.push(final_token);
-        self.tokens.clone()
+    fn error(&mut self, error: GloxError) {
+        self.pending.push_back(Err(error));
     }
 
     fn scan_token(&mut self) {
@@ -52,14 +137,38 @@ impl Scanner {
             // Single character symbols
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
+            '{' => {
+                if let Some(open) = self.open_strings.last_mut() {
+                    open.depth += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            '}' => match self.open_strings.pop() {
+                Some(open) if open.depth == 0 => self.handle_string(open.quotes, open.opening),
+                Some(mut open) => {
+                    open.depth -= 1;
+                    self.open_strings.push(open);
+                    self.add_token(TokenType::RightBrace)
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
             ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
+            ':' => self.add_token(TokenType::Colon),
+            '.' => {
+                let peek: char = self.peek();
+                if self.is_digit(peek) {
+                    self.handle_leading_dot();
+                } else {
+                    self.add_token(TokenType::Dot)
+                }
+            }
             '-' => self.add_token(TokenType::Minus),
             '+' => self.add_token(TokenType::Plus),
             ';' => self.add_token(TokenType::Semicolon),
             '*' => self.add_token(TokenType::Star),
+            '%' => self.add_token(TokenType::Percent),
             // One or two character symbols
             '!' => {
                 if self.check('=') {
@@ -89,12 +198,20 @@ impl Scanner {
                     self.add_token(TokenType::Greater)
                 }
             }
+            '~' => {
+                if self.check('/') {
+                    self.add_token(TokenType::TildeSlash)
+                } else {
+                    self.error(GloxError::unexpected_character(c, self.span()));
+                }
+            }
             // Check for comments
             '/' => {
                 if self.check('/') {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.comments.push(self.span());
                 } else {
                     self.add_token(TokenType::Slash)
                 }
@@ -104,70 +221,94 @@ impl Scanner {
             '\r' => {}
             '\t' => {}
             // New lines
-            '\n' => self.line += 1,
+            '\n' => self.newline(),
             // String literals
-            '"' => self.handle_string(),
+            '"' => self.open_string(false),
             // Numbers, identifiers, and keywords
             _ => {
-                if self.is_digit(c) {
-                    self.handle_number();
+                if c == 'r' && self.peek() == '"' {
+                    self.advance();
+                    self.open_string(true);
+                } else if self.is_digit(c) {
+                    self.handle_number(c);
                 } else if self.is_alpha(c) {
                     self.handle_identifier();
                 } else {
-                    eprintln!("Unexpected character: {c}")
+                    self.error(GloxError::unexpected_character(c, self.span()));
                 }
             }
         }
     }
 
     fn advance(&mut self) -> char {
-        // Get the current character then move the index up by one
-        let current_char = self.chars[self.current];
-        self.current += 1;
-        return current_char;
+        // Get the current character then move past all of its bytes
+        let current_char = self.peek();
+        self.current += current_char.len_utf8();
+        self.column += 1;
+        current_char
+    }
+
+    // Called after consuming a newline character
+    fn newline(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    // An empty span at the current character
+    fn here(&self) -> Span {
+        Span {
+            start: self.current,
+            end: self.current,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.current,
+            line: self.start_line,
+            column: self.start_column,
+        }
     }
 
     fn check(&mut self, c: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        } else if self.chars[self.current] != c {
+        if self.is_at_end() || self.peek() != c {
             return false;
         }
-        self.current += 1;
-        return true;
+        self.advance();
+        true
     }
 
     fn peek(&mut self) -> char {
-        if self.is_at_end() {
-            return '\0';
+        match self.source.as_bytes().get(self.current) {
+            None => '\0',
+            Some(&byte) if byte.is_ascii() => byte as char,
+            Some(_) => self.source[self.current..].chars().next().unwrap(),
         }
-        self.chars[self.current]
     }
 
     fn peek_next(&mut self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.chars[self.current + 1]
+        self.source[self.current..].chars().nth(1).unwrap_or('\0')
     }
 
     fn is_at_end(&mut self) -> bool {
-        if self.current == self.chars.len() {
-            return true;
-        }
-        return false;
+        self.current >= self.source.len()
     }
 
     fn is_digit(&mut self, c: char) -> bool {
-        return c >= '0' && c <= '9';
+        c.is_ascii_digit()
     }
 
+    // Identifiers follow UAX #31, the same rules as Rust's, so they can be
+    // written in any script: naïve, 変数, π
     fn is_alpha(&mut self, c: char) -> bool {
-        return (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_';
+        c == '_' || unicode_ident::is_xid_start(c)
     }
 
     fn is_alphanumeric(&mut self, c: char) -> bool {
-        return self.is_alpha(c) || self.is_digit(c);
+        unicode_ident::is_xid_continue(c)
     }
 
     fn add_token(&mut self, token_type: TokenType) {
@@ -176,66 +317,273 @@ impl Scanner {
 
     // For handling floats and bools
     fn add_token_literal(&mut self, token_type: TokenType, literal: Literal) {
-        let lexeme = self.source[self.start..self.current].to_string();
-        self.tokens.push(Token {
-            token_type: token_type,
-            lexeme: lexeme,
-            literal: literal,
-            line: self.line,
-        })
+        self.pending.push_back(Ok(Token {
+            token_type,
+            lexeme: &self.source[self.start..self.current],
+            literal,
+            span: self.span(),
+        }))
     }
 
-    fn add_token_string(&mut self) {
-        // Trim the quotes
-        let lexeme = self.source[self.start + 1..self.current - 1].to_string();
-        self.tokens.push(Token {
-            token_type: TokenType::String,
-            lexeme: lexeme.clone(),
-            literal: Literal::Str(lexeme),
-            line: self.line,
-        })
+    // Called after the opening quote, and the r of a raw string
+    fn open_string(&mut self, raw: bool) {
+        let triple = self.peek() == '"' && self.peek_next() == '"';
+        if triple {
+            self.advance();
+            self.advance();
+        }
+        self.handle_string(Quotes { raw, triple }, self.span());
     }
 
-    fn handle_string(&mut self) {
-        // Handle multi-line strings
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1
+    // Scans up to the closing quote, or up to a `${` in which case the rest of
+    // the string is scanned once the expression inside has been
+    fn handle_string(&mut self, quotes: Quotes, opening: Span) {
+        let mut value = String::new();
+        loop {
+            // Strings can span multiple lines
+            if self.is_at_end() {
+                let error = self.unterminated_string(opening, quotes);
+                self.error(error);
+                return;
+            }
+            let here: Span = self.here();
+            let c: char = self.advance();
+            match c {
+                '"' if !quotes.triple => break,
+                '"' if self.peek() == '"' && self.peek_next() == '"' => {
+                    self.advance();
+                    self.advance();
+                    break;
+                }
+                '\\' if !quotes.raw => {
+                    if let Some(escaped) = self.escape(here) {
+                        value.push(escaped);
+                    }
+                }
+                '$' if !quotes.raw && self.peek() == '{' => {
+                    self.advance();
+                    self.add_token_literal(TokenType::Interpolation, Literal::Str(value));
+                    self.open_strings.push(OpenString {
+                        quotes,
+                        opening,
+                        depth: 0,
+                    });
+                    return;
+                }
+                // Line breaks in a string are \n whatever the file uses
+                '\r' if self.peek() == '\n' => {}
+                '\n' => {
+                    self.newline();
+                    value.push(c);
+                }
+                _ => value.push(c),
             }
-            self.advance();
         }
-        // Error for no closing quote
+        self.add_token_literal(TokenType::String, Literal::Str(value));
+    }
+
+    // Called after a backslash, which starts at the given span
+    fn escape(&mut self, mut span: Span) -> Option<char> {
+        // An unterminated string, which handle_string reports
         if self.is_at_end() {
-            eprintln!("Untermintated string.")
+            return None;
+        }
+        let escaped = match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '$' => Some('$'),
+            'u' => {
+                let escaped = self.unicode_escape();
+                if escaped.is_none() {
+                    span.end = self.current;
+                    self.error(
+                        GloxError::invalid_escape("Invalid unicode escape.", span).with_help(
+                            "write the code point in hex between braces, as in \\u{1F600}",
+                        ),
+                    );
+                }
+                return escaped;
+            }
+            // Newlines still need counting
+            '\n' => {
+                self.newline();
+                None
+            }
+            _ => None,
+        };
+        if escaped.is_none() {
+            span.end = self.current;
+            let sequence = &self.source[span.start..span.end];
+            self.error(
+                GloxError::invalid_escape(format!("Invalid escape sequence '{}'.", sequence), span)
+                    .with_help(
+                        "the escapes are \\n \\t \\r \\0 \\\\ \\\" \\$ and \\u{...}, \
+                         or use a raw string r\"...\" to keep backslashes as written",
+                    ),
+            );
+        }
+        escaped
+    }
+
+    // The {...} of a \u{...} escape, one to six hex digits naming a code point
+    fn unicode_escape(&mut self) -> Option<char> {
+        if self.peek() != '{' {
+            return None;
         }
-        // Handle closing quote
         self.advance();
-        // Add the token
-        self.add_token_string();
+        let start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let end = self.current;
+        if self.peek() != '}' {
+            return None;
+        }
+        self.advance();
+        let digits = &self.source[start..end];
+        if digits.is_empty() || digits.len() > 6 {
+            return None;
+        }
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
     }
 
-    fn handle_number(&mut self) {
-        // Handle initial digits
-        let mut peek: char = self.peek();
-        while self.is_digit(peek) {
+    fn unterminated_string(&self, opening: Span, quotes: Quotes) -> GloxError {
+        let closing = if quotes.triple { "\"\"\"" } else { "\"" };
+        GloxError::unterminated_string(opening)
+            .with_help(format!("add a closing '{}' to end the string", closing))
+    }
+
+    // Numbers are decimal (with an optional fraction and exponent, 1.5e-3), or ints
+    // in hexadecimal (0xFF), binary (0b1010) or octal (0o17). Underscores can go
+    // between digits to group them, as in 1_000_000.
+    fn handle_number(&mut self, first: char) {
+        let radix = match self.peek() {
+            'x' | 'X' if first == '0' => Some((16, "a hexadecimal")),
+            'b' | 'B' if first == '0' => Some((2, "a binary")),
+            'o' | 'O' if first == '0' => Some((8, "an octal")),
+            _ => None,
+        };
+        if let Some((radix, kind)) = radix {
             self.advance();
-            peek = self.peek();
+            if !self.digits(self.current, radix, kind) {
+                return;
+            }
+            let digits = without_underscores(&self.source[self.start + 2..self.current]);
+            match i64::from_str_radix(&digits, radix) {
+                Ok(int_value) => self.add_token_literal(TokenType::Number, Literal::Int(int_value)),
+                Err(_) => self.int_too_large(),
+            }
+            return;
         }
 
+        // The first digit has already been consumed
+        if !self.digits(self.start, 10, "a decimal") {
+            return;
+        }
+        let mut is_float = false;
+
         // Check for a decimal and keep going if numbers follow it
         let peek_next: char = self.peek_next();
         if self.peek() == '.' && self.is_digit(peek_next) {
+            is_float = true;
+            self.advance();
+            if !self.digits(self.current, 10, "a decimal") {
+                return;
+            }
+        }
+
+        if matches!(self.peek(), 'e' | 'E') {
+            is_float = true;
             self.advance();
-            let mut peek: char = self.peek();
-            while self.is_digit(peek) {
+            if matches!(self.peek(), '+' | '-') {
                 self.advance();
-                peek = self.peek();
+            }
+            if !self.digits(self.current, 10, "a decimal") {
+                return;
             }
         }
 
-        let number = self.source[self.start..self.current].to_string();
-        let float_value = number.parse::<f32>().unwrap();
-        self.add_token_literal(TokenType::Number, Literal::Float(float_value));
+        let number = without_underscores(&self.source[self.start..self.current]);
+        if is_float {
+            let float_value = number.parse::<f64>().unwrap();
+            if float_value.is_infinite() {
+                self.error(GloxError::invalid_number(
+                    "Float literal is too large.",
+                    self.span(),
+                ));
+                return;
+            }
+            self.add_token_literal(TokenType::Number, Literal::Float(float_value));
+            return;
+        }
+        match number.parse::<i64>() {
+            Ok(int_value) => self.add_token_literal(TokenType::Number, Literal::Int(int_value)),
+            Err(_) => self.int_too_large(),
+        }
+    }
+
+    // Consumes the digits of a number from start, which may already have been
+    // consumed, up to the current character. Letters are swallowed too so that a
+    // typo like 0b102 or 12px is reported as one bad number. Returns false after
+    // reporting an error.
+    fn digits(&mut self, start: usize, radix: u32, kind: &str) -> bool {
+        let mut peek: char = self.peek();
+        while peek.is_ascii_alphanumeric() || peek == '_' {
+            // The exponent of a decimal is scanned separately
+            if radix == 10 && matches!(peek, 'e' | 'E') {
+                break;
+            }
+            self.advance();
+            peek = self.peek();
+        }
+
+        let text = &self.source[start..self.current];
+        let error = if text.is_empty() {
+            format!(
+                "Expected digits after '{}'.",
+                &self.source[self.start..start]
+            )
+        } else if let Some(c) = text.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+            format!("Invalid digit '{}' for {} number.", c, kind)
+        } else if text.starts_with('_') || text.ends_with('_') || text.contains("__") {
+            "Underscores in a number must go between digits.".to_string()
+        } else {
+            return true;
+        };
+        self.error(GloxError::invalid_number(error, self.span()));
+        false
+    }
+
+    fn int_too_large(&mut self) {
+        self.error(
+            GloxError::invalid_number("Integer literal is too large.", self.span()).with_help(
+                format!(
+                    "the largest int is {}, add '.0' to make it a float",
+                    i64::MAX
+                ),
+            ),
+        );
+    }
+
+    // Called on a '.' followed by a digit, which is a mistake rather than a
+    // property access since property names can't start with a digit
+    fn handle_leading_dot(&mut self) {
+        let mut peek: char = self.peek();
+        while peek.is_ascii_alphanumeric() || peek == '_' {
+            self.advance();
+            peek = self.peek();
+        }
+        let number = &self.source[self.start..self.current];
+        let help = format!("add a zero before the '.', as in 0{}", number);
+        self.error(
+            GloxError::invalid_number("Numbers can't start with '.'.", self.span()).with_help(help),
+        );
     }
 
     fn handle_identifier(&mut self) {
@@ -244,13 +592,18 @@ impl Scanner {
             self.advance();
             peek = self.peek();
         }
-        let text: String = self.source[self.start..self.current].to_string();
+        let text: &str = &self.source[self.start..self.current];
         // Try to match the text to a keyword otherwise it's an Identifier
-        let token_type: TokenType = self
-            .keywords
-            .get(&text)
-            .copied()
-            .unwrap_or(TokenType::Identifier);
+        let token_type: TokenType = token::keyword(text).unwrap_or(TokenType::Identifier);
         self.add_token(token_type);
     }
 }
+
+// Underscores only group digits, so they're dropped before the number is parsed
+fn without_underscores(text: &str) -> Cow<'_, str> {
+    if text.contains('_') {
+        Cow::Owned(text.replace('_', ""))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
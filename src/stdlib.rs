@@ -0,0 +1,334 @@
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::interpreter::Environment;
+use crate::map::{LoxMap, missing_key};
+use crate::native::{FromValue, IntoNative};
+use crate::object::{Value, list_index};
+
+// The functions every program starts with, defined as globals by Interpreter::new.
+// Strings are indexed and measured in characters. Lists and maps are changed in
+// place, and list indices can be negative to count back from the end.
+//
+//   clock()                   seconds since the interpreter started
+//   input()                   the next line of stdin, or nil at the end of input
+//   len(x)                    the length of a string, list or map
+//   str(x)                    x as it would be printed
+//   num(x)                    a string parsed as a number, or nil if it isn't one
+//   type(x)                   "int", "float", "string", "bool", "nil", "function",
+//                             "class", "instance", "list" or "map"
+//   sqrt(x), floor(x), pow(x, y)
+//   abs(x), min(x, y), max(x, y)  an int if every argument is one
+//   random()                  a number in [0, 1)
+//   seed(n)                   restarts random() at a repeatable sequence
+//   substr(s, start, length)  part of a string, an error if it runs past the end
+//   index_of(s, part)         where part first appears in s, or -1
+//   upper(s), lower(s), trim(s)
+//   split(s, separator)       a list of the parts of s, or of its characters if
+//                             the separator is ""
+//   push(xs, x), pop(xs)      add to or take from the end of a list
+//   insert(xs, i, x)          put x before index i, which can be len(xs)
+//   remove(xs, i)             take out the element at i and return it
+//   remove(m, key)            take out the value for key and return it
+//   slice(xs, start, end)     a new list of the elements from start up to end
+//   contains(xs, x)           whether some element == x
+//   reverse(xs)
+//   sort(xs)                  only for lists of just numbers or just strings
+//   has(m, key)               whether the map has a value for key
+//   keys(m), values(m)        lists of a map's keys or values, in the order the
+//                             keys were first added
+pub fn define_globals(globals: &mut Environment) {
+    let start = Instant::now();
+    define(globals, "clock", move || start.elapsed().as_secs_f64());
+    define(globals, "input", input);
+
+    define(globals, "len", len);
+    define(globals, "str", |x: Value| x.to_string());
+    define(globals, "num", num);
+    define(globals, "type", |x: Value| x.type_name());
+
+    define(globals, "sqrt", f64::sqrt);
+    define(globals, "floor", f64::floor);
+    define(globals, "pow", f64::powf);
+    define(globals, "abs", abs);
+    define(globals, "min", |x: Value, y: Value| {
+        pick("min", x, y, i64::min, f64::min)
+    });
+    define(globals, "max", |x: Value, y: Value| {
+        pick("max", x, y, i64::max, f64::max)
+    });
+
+    let state = Rc::new(Cell::new(seed_from_time()));
+    let random_state = Rc::clone(&state);
+    define(globals, "random", move || random(&random_state));
+    define(globals, "seed", move |n: f64| {
+        state.set(splitmix64(n.to_bits()))
+    });
+
+    define(globals, "substr", substr);
+    define(globals, "index_of", index_of);
+    define(globals, "upper", |s: Rc<str>| s.to_uppercase());
+    define(globals, "lower", |s: Rc<str>| s.to_lowercase());
+    define(globals, "trim", |s: Rc<str>| s.trim().to_string());
+    define(globals, "split", split);
+
+    define(globals, "push", |xs: List, x: Value| {
+        xs.borrow_mut().push(x)
+    });
+    define(globals, "pop", |xs: List| {
+        xs.borrow_mut().pop().ok_or("Can't pop from an empty list.")
+    });
+    define(globals, "insert", insert);
+    define(globals, "remove", remove);
+    define(globals, "slice", slice);
+    define(globals, "contains", |xs: List, x: Value| {
+        xs.borrow().iter().any(|element| element.is_equal(&x))
+    });
+    define(globals, "reverse", |xs: List| xs.borrow_mut().reverse());
+    define(globals, "sort", sort);
+
+    define(globals, "has", |m: Map, key: Value| {
+        m.borrow().get(&key).map(|v| v.is_some())
+    });
+    define(globals, "keys", |m: Map| m.borrow().keys());
+    define(globals, "values", |m: Map| m.borrow().values());
+}
+
+type List = Rc<RefCell<Vec<Value>>>;
+type Map = Rc<RefCell<LoxMap>>;
+
+fn define<Args>(globals: &mut Environment, name: &str, function: impl IntoNative<Args>) {
+    globals.define(name, Value::Native(Rc::new(function.into_native(name))));
+}
+
+fn input() -> Result<Option<String>, io::Error> {
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let trimmed = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(trimmed);
+    Ok(Some(line))
+}
+
+fn len(x: Value) -> Result<i64, String> {
+    match x {
+        Value::Str(s) => Ok(s.chars().count() as i64),
+        Value::List(xs) => Ok(xs.borrow().len() as i64),
+        Value::Map(m) => Ok(m.borrow().len() as i64),
+        x => Err(format!(
+            "Expected a string, list or map for argument 1 of 'len' but got {}.",
+            x.type_name()
+        )),
+    }
+}
+
+// Strings that look like ints become ints
+fn num(x: Value) -> Result<Option<Value>, String> {
+    match x {
+        Value::Int(_) | Value::Float(_) => Ok(Some(x)),
+        Value::Str(s) => {
+            let s = s.trim();
+            if let Ok(i) = s.parse::<i64>() {
+                return Ok(Some(Value::Int(i)));
+            }
+            Ok(s.parse::<f64>().ok().map(Value::Float))
+        }
+        x => Err(format!("Can't convert {} to a number.", x.type_name())),
+    }
+}
+
+fn abs(x: Value) -> Result<Value, String> {
+    match x {
+        Value::Int(i) => i
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or_else(|| "Integer overflow.".to_string()),
+        Value::Float(f) => Ok(Value::Float(f.abs())),
+        x => Err(not_a_number("abs", 1, &x)),
+    }
+}
+
+// For min and max, which stay exact when given two ints
+fn pick(
+    name: &str,
+    x: Value,
+    y: Value,
+    ints: fn(i64, i64) -> i64,
+    floats: fn(f64, f64) -> f64,
+) -> Result<Value, String> {
+    match (&x, &y) {
+        (Value::Int(a), Value::Int(b)) => Ok(Value::Int(ints(*a, *b))),
+        _ => {
+            let a = f64::from_value(&x).ok_or_else(|| not_a_number(name, 1, &x))?;
+            let b = f64::from_value(&y).ok_or_else(|| not_a_number(name, 2, &y))?;
+            Ok(Value::Float(floats(a, b)))
+        }
+    }
+}
+
+// Matches the error for arguments that don't convert, see native.rs
+fn not_a_number(name: &str, n: usize, x: &Value) -> String {
+    format!(
+        "Expected a number for argument {} of '{}' but got {}.",
+        n,
+        name,
+        x.type_name()
+    )
+}
+
+fn substr(s: Rc<str>, start: i64, length: i64) -> Result<String, String> {
+    let start = to_index(start, "start")?;
+    let length = to_index(length, "length")?;
+    let chars = s.chars().count();
+    if start + length > chars {
+        return Err(format!(
+            "substr range {}..{} is out of bounds for a string of length {}.",
+            start,
+            start + length,
+            chars
+        ));
+    }
+    Ok(s.chars().skip(start).take(length).collect())
+}
+
+fn index_of(s: Rc<str>, part: Rc<str>) -> i64 {
+    match s.find(&*part) {
+        Some(byte) => s[..byte].chars().count() as i64,
+        None => -1,
+    }
+}
+
+fn split(s: Rc<str>, separator: Rc<str>) -> Vec<Value> {
+    if separator.is_empty() {
+        return s
+            .chars()
+            .map(|c| Value::Str(c.to_string().into()))
+            .collect();
+    }
+    s.split(&*separator)
+        .map(|part| Value::Str(part.into()))
+        .collect()
+}
+
+fn insert(xs: List, index: i64, x: Value) -> Result<(), String> {
+    let mut xs = xs.borrow_mut();
+    let i = bound(index, xs.len()).ok_or_else(|| out_of_bounds(index, xs.len()))?;
+    xs.insert(i, x);
+    Ok(())
+}
+
+fn remove(x: Value, key: Value) -> Result<Value, String> {
+    match (x, key) {
+        (Value::List(xs), index) => {
+            let index = i64::from_value(&index).ok_or_else(|| {
+                format!(
+                    "Expected an integer for argument 2 of 'remove' but got {}.",
+                    index.type_name()
+                )
+            })?;
+            let mut xs = xs.borrow_mut();
+            let i = list_index(index, xs.len()).ok_or_else(|| out_of_bounds(index, xs.len()))?;
+            Ok(xs.remove(i))
+        }
+        (Value::Map(m), key) => m
+            .borrow_mut()
+            .remove(&key)?
+            .ok_or_else(|| missing_key(&key)),
+        (x, _) => Err(format!(
+            "Expected a list or map for argument 1 of 'remove' but got {}.",
+            x.type_name()
+        )),
+    }
+}
+
+fn out_of_bounds(index: i64, len: usize) -> String {
+    format!(
+        "Index {} is out of bounds for a list of length {}.",
+        index, len
+    )
+}
+
+fn slice(xs: List, start: i64, end: i64) -> Result<Vec<Value>, String> {
+    let xs = xs.borrow();
+    match (bound(start, xs.len()), bound(end, xs.len())) {
+        (Some(from), Some(to)) if from <= to => Ok(xs[from..to].to_vec()),
+        _ => Err(format!(
+            "slice range {}..{} is out of bounds for a list of length {}.",
+            start,
+            end,
+            xs.len()
+        )),
+    }
+}
+
+// Like list_index, except that it can also be len itself, for the positions
+// between elements used by insert and slice
+fn bound(index: i64, len: usize) -> Option<usize> {
+    if index == len as i64 {
+        return Some(len);
+    }
+    list_index(index, len)
+}
+
+// Numbers sort by value, ints and floats together, and strings by code point
+fn sort(xs: List) -> Result<(), String> {
+    let mut xs = xs.borrow_mut();
+    let numbers = xs
+        .iter()
+        .all(|x| matches!(x, Value::Int(_) | Value::Float(_)));
+    let strings = xs.iter().all(|x| matches!(x, Value::Str(_)));
+    if !numbers && !strings {
+        return Err("Can only sort lists of just numbers or just strings.".to_string());
+    }
+    xs.sort_by(|a, b| match (a, b) {
+        (Value::Int(a), Value::Int(b)) => a.cmp(b),
+        (Value::Str(a), Value::Str(b)) => a.cmp(b),
+        _ => {
+            let a = f64::from_value(a).unwrap_or(f64::NAN);
+            let b = f64::from_value(b).unwrap_or(f64::NAN);
+            a.total_cmp(&b)
+        }
+    });
+    Ok(())
+}
+
+fn to_index(n: i64, what: &str) -> Result<usize, String> {
+    usize::try_from(n).map_err(|_| {
+        format!(
+            "substr {} must be a whole number that isn't negative, got {}.",
+            what, n
+        )
+    })
+}
+
+// random() is xorshift64*, which is plenty for scripts and keeps the
+// sequence reproducible across platforms once seeded
+
+fn random(state: &Cell<u64>) -> f64 {
+    let mut x = state.get();
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    state.set(x);
+    // The top 53 bits fill an f64's mantissa exactly, keeping the result below 1
+    let bits = x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+// Spreads the seed's bits out, and never returns the one state xorshift can't leave
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)).max(1)
+}
+
+fn seed_from_time() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    splitmix64(nanos)
+}
@@ -0,0 +1,119 @@
+use std::rc::Rc;
+
+use crate::expr::{Expr, Variable};
+use crate::token::AstToken;
+
+pub trait StmtVisitor<T> {
+    fn visit_expression_stmt(&mut self, stmt: &ExpressionStmt) -> T;
+    fn visit_print_stmt(&mut self, stmt: &PrintStmt) -> T;
+    fn visit_var_stmt(&mut self, stmt: &VarStmt) -> T;
+    fn visit_block_stmt(&mut self, stmt: &BlockStmt) -> T;
+    fn visit_if_stmt(&mut self, stmt: &IfStmt) -> T;
+    fn visit_while_stmt(&mut self, stmt: &WhileStmt) -> T;
+    fn visit_for_stmt(&mut self, stmt: &ForStmt) -> T;
+    fn visit_function_stmt(&mut self, stmt: &Rc<FunctionStmt>) -> T;
+    fn visit_return_stmt(&mut self, stmt: &ReturnStmt) -> T;
+    fn visit_class_stmt(&mut self, stmt: &ClassStmt) -> T;
+}
+
+#[derive(Clone, Debug)]
+pub enum Stmt {
+    Expression(ExpressionStmt),
+    Print(PrintStmt),
+    Var(VarStmt),
+    Block(BlockStmt),
+    If(IfStmt),
+    While(WhileStmt),
+    For(ForStmt),
+    Function(Rc<FunctionStmt>),
+    Return(ReturnStmt),
+    Class(ClassStmt),
+}
+
+impl Stmt {
+    pub fn accept<T>(&self, visitor: &mut impl StmtVisitor<T>) -> T {
+        match self {
+            Stmt::Expression(x) => visitor.visit_expression_stmt(x),
+            Stmt::Print(x) => visitor.visit_print_stmt(x),
+            Stmt::Var(x) => visitor.visit_var_stmt(x),
+            Stmt::Block(x) => visitor.visit_block_stmt(x),
+            Stmt::If(x) => visitor.visit_if_stmt(x),
+            Stmt::While(x) => visitor.visit_while_stmt(x),
+            Stmt::For(x) => visitor.visit_for_stmt(x),
+            Stmt::Function(x) => visitor.visit_function_stmt(x),
+            Stmt::Return(x) => visitor.visit_return_stmt(x),
+            Stmt::Class(x) => visitor.visit_class_stmt(x),
+        }
+    }
+}
+
+// An expression evaluated for its side effects, the result is discarded
+#[derive(Clone, Debug)]
+pub struct ExpressionStmt {
+    pub expression: Expr,
+}
+
+#[derive(Clone, Debug)]
+pub struct PrintStmt {
+    pub expression: Expr,
+}
+
+// A variable declaration, variables without an initializer start out as nil
+#[derive(Clone, Debug)]
+pub struct VarStmt {
+    pub name: AstToken,
+    pub initializer: Option<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockStmt {
+    pub statements: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug)]
+pub struct IfStmt {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct WhileStmt {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+}
+
+// Kept as its own statement rather than desugared into
+// `{ init; while (cond) { body; incr; } }` so the formatter and the ast command
+// can print it back the way it was written. The backends run it exactly like
+// that while loop: the initializer's variable is scoped to the loop and shared
+// by every iteration. Every clause is optional, a missing condition loops forever.
+#[derive(Clone, Debug)]
+pub struct ForStmt {
+    pub initializer: Option<Box<Stmt>>,
+    pub condition: Option<Expr>,
+    pub increment: Option<Expr>,
+    pub body: Box<Stmt>,
+}
+
+// Shared behind an Rc so every function value created from the declaration
+// can point at it without copying the body
+#[derive(Clone, Debug)]
+pub struct FunctionStmt {
+    pub name: AstToken,
+    pub params: Vec<AstToken>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReturnStmt {
+    pub keyword: AstToken,
+    pub value: Option<Expr>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClassStmt {
+    pub name: AstToken,
+    pub superclass: Option<Variable>,
+    pub methods: Vec<Rc<FunctionStmt>>,
+}
@@ -1,46 +1,104 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
 
-pub fn get_keywords() -> HashMap<String, TokenType> {
-    let mut keywords = HashMap::new();
-    keywords.insert("and".to_string(), TokenType::And);
-    keywords.insert("class".to_string(), TokenType::Class);
-    keywords.insert("else".to_string(), TokenType::Else);
-    keywords.insert("false".to_string(), TokenType::False);
-    keywords.insert("fun".to_string(), TokenType::Fun);
-    keywords.insert("for".to_string(), TokenType::For);
-    keywords.insert("if".to_string(), TokenType::If);
-    keywords.insert("nil".to_string(), TokenType::Nil);
-    keywords.insert("or".to_string(), TokenType::Or);
-    keywords.insert("print".to_string(), TokenType::Print);
-    keywords.insert("return".to_string(), TokenType::Return);
-    keywords.insert("super".to_string(), TokenType::Super);
-    keywords.insert("this".to_string(), TokenType::This);
-    keywords.insert("true".to_string(), TokenType::True);
-    keywords.insert("var".to_string(), TokenType::Var);
-    keywords.insert("while".to_string(), TokenType::While);
-    keywords
+use crate::object::format_number;
+
+// The keyword spelled by text, if there is one
+pub fn keyword(text: &str) -> Option<TokenType> {
+    let token_type = match text {
+        "and" => TokenType::And,
+        "class" => TokenType::Class,
+        "else" => TokenType::Else,
+        "false" => TokenType::False,
+        "fun" => TokenType::Fun,
+        "for" => TokenType::For,
+        "if" => TokenType::If,
+        "nil" => TokenType::Nil,
+        "or" => TokenType::Or,
+        "print" => TokenType::Print,
+        "return" => TokenType::Return,
+        "super" => TokenType::Super,
+        "this" => TokenType::This,
+        "true" => TokenType::True,
+        "var" => TokenType::Var,
+        "while" => TokenType::While,
+        _ => return None,
+    };
+    Some(token_type)
 }
 
+// The value of a literal as written in the source, attached to its token by the scanner
 #[derive(Debug, Clone)]
 pub enum Literal {
-    Float(f32),
+    // Number literals without a fractional part are ints
+    Int(i64),
+    Float(f64),
     Str(String),
     Bool(bool),
     Nil,
 }
 
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Int(i) => write!(f, "{}", i),
+            Literal::Float(x) => write!(f, "{}", format_number(*x)),
+            Literal::Str(s) => write!(f, "{}", s),
+            Literal::Bool(b) => write!(f, "{}", b),
+            Literal::Nil => write!(f, "nil"),
+        }
+    }
+}
+
+// A region of the source code. Offsets are in bytes and the end is exclusive,
+// the line and column (counted in characters) are those of the start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    // The smallest span covering both self and other
+    pub fn to(self, other: Span) -> Span {
+        let (first, last) = if self.start <= other.start {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Span {
+            start: first.start,
+            end: first.end.max(last.end),
+            line: first.line,
+            column: first.column,
+        }
+    }
+}
+
+// A token as the scanner produces it, its lexeme is a slice of the source
 #[derive(Debug, Clone)]
-pub struct Token {
+pub struct Token<'a> {
     pub token_type: TokenType,
-    pub lexeme: String,
+    pub lexeme: &'a str,
     pub literal: Literal,
-    pub line: usize,
+    pub span: Span,
 }
 
-impl Token {}
+// A token kept in the syntax tree, which outlives the source it was parsed from
+// and so can't borrow from it. The parser shares one copy of each lexeme
+// between the tokens spelled the same way, so cloning one is cheap.
+#[derive(Debug, Clone)]
+pub struct AstToken {
+    pub token_type: TokenType,
+    pub lexeme: Rc<str>,
+    pub span: Span,
+}
 
 // TokenType contains all of the elements in the lexical grammar
 // of the Lox language.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     // Single character tokens
@@ -48,13 +106,17 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Percent,
     // One or two character tokens
     Bang,
     BangEqual,
@@ -64,9 +126,13 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    // Integer division, `//` would start a comment
+    TildeSlash,
     // Literals
     Identifier,
     String,
+    // The part of a string before a `${`, see Parser::interpolation
+    Interpolation,
     Number,
     // Keywords
     And,
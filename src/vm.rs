@@ -0,0 +1,690 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::chunk::{Function, OpCode};
+use crate::error::GloxError;
+use crate::function::{LoxCallable, MAX_CALL_DEPTH, stack_overflow};
+use crate::interpreter::Environment;
+use crate::map::LoxMap;
+use crate::object::{NativeFunction, Value};
+use crate::operators::{binary, get_index, negate, set_index};
+use crate::stdlib;
+use crate::token::{Span, TokenType};
+
+// A stack based virtual machine for code from the Compiler, in the style of clox.
+// Every value of the running program lives on one stack: each call gets a window
+// onto it with the callee in slot 0, then the arguments, then its locals.
+#[derive(Default)]
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Rc<str>, Value>,
+    // Upvalues still pointing into the stack, sorted by slot, so closures
+    // created in the same scope share them
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    // Where in the closure's code to carry on from when the frame is resumed
+    ip: usize,
+    // The stack index of the frame's slot 0
+    slots: usize,
+}
+
+// A function along with the variables it captured from the functions around it
+pub struct Closure {
+    pub function: Rc<Function>,
+    upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+// A captured variable. It stays on the stack while the function that declared it
+// is running, and moves into the upvalue when it goes out of scope.
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct VmClass {
+    pub name: Rc<str>,
+    methods: RefCell<HashMap<Rc<str>, Rc<Closure>>>,
+}
+
+pub struct VmInstance {
+    pub class: Rc<VmClass>,
+    fields: HashMap<Rc<str>, Value>,
+}
+
+// A method along with the instance it was accessed on, which becomes `this`
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        // The standard library defines into an environment, like it does for the tree-walker
+        let mut natives = Environment::new();
+        stdlib::define_globals(&mut natives);
+        let mut vm = Vm::default();
+        for (name, value) in natives.bindings() {
+            vm.define_global(&name, value);
+        }
+        vm
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(Rc::from(name), value);
+    }
+
+    // Every global, sorted by name
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut globals: Vec<(String, Value)> = self
+            .globals
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+        globals
+    }
+
+    // Runs a compiled script and returns what it returned. After a runtime error
+    // the stack is thrown away, but globals defined up to that point are kept.
+    pub fn interpret(&mut self, script: Rc<Function>) -> Result<Value, GloxError> {
+        let closure = Rc::new(Closure {
+            function: script,
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(Rc::clone(&closure)));
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: 0,
+        });
+        let result = self.run();
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<Value, GloxError> {
+        let frame = self.frames.last().unwrap();
+        let mut closure: Rc<Closure> = Rc::clone(&frame.closure);
+        let mut ip: usize = frame.ip;
+        let mut slots: usize = frame.slots;
+
+        loop {
+            let chunk = &closure.function.chunk;
+            let code = &chunk.code;
+            // Where the current instruction starts, for looking up its spans
+            let start = ip;
+            let op = OpCode::from_byte(code[ip]);
+            ip += 1;
+
+            macro_rules! read_byte {
+                () => {{
+                    ip += 1;
+                    code[ip - 1]
+                }};
+            }
+            macro_rules! read_u16 {
+                () => {{
+                    ip += 2;
+                    chunk.read_u16(ip - 2)
+                }};
+            }
+            macro_rules! read_constant {
+                () => {
+                    &chunk.constants[read_u16!() as usize]
+                };
+            }
+            macro_rules! read_name {
+                () => {
+                    match read_constant!() {
+                        Value::Str(name) => Rc::clone(name),
+                        _ => unreachable!("names are always string constants"),
+                    }
+                };
+            }
+            macro_rules! binary_op {
+                ($operator:expr) => {{
+                    let right = self.pop();
+                    let left = self.pop();
+                    // The span is only looked up if there's an error to point it at
+                    let value = binary($operator, left, right, Span::default())
+                        .map_err(|e| e.or_span(chunk.span(start, 0)))?;
+                    self.stack.push(value);
+                }};
+            }
+            // Saves the ip of the current frame before switching to another one
+            macro_rules! switch_frame {
+                () => {{
+                    self.frames.last_mut().unwrap().ip = ip;
+                }};
+            }
+            macro_rules! load_frame {
+                () => {{
+                    let frame = self.frames.last().unwrap();
+                    closure = Rc::clone(&frame.closure);
+                    ip = frame.ip;
+                    slots = frame.slots;
+                }};
+            }
+
+            match op {
+                OpCode::Constant => {
+                    let constant = read_constant!().clone();
+                    self.stack.push(constant);
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = read_byte!() as usize;
+                    self.stack.push(self.stack[slots + slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = read_byte!() as usize;
+                    self.stack[slots + slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = read_name!();
+                    match self.globals.get(&name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(undefined_variable(&name, chunk.span(start, 0))),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = read_name!();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = read_name!();
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(slot) => *slot = value,
+                        None => return Err(undefined_variable(&name, chunk.span(start, 0))),
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let slot = read_byte!() as usize;
+                    let value = match &*closure.upvalues[slot].borrow() {
+                        Upvalue::Open(index) => self.stack[*index].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let slot = read_byte!() as usize;
+                    let value = self.peek(0).clone();
+                    match &mut *closure.upvalues[slot].borrow_mut() {
+                        Upvalue::Open(index) => self.stack[*index] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = read_name!();
+                    let span = chunk.span(start, 0);
+                    let object = self.pop();
+                    let value = match &object {
+                        Value::VmInstance(instance) => {
+                            let field = instance.borrow().fields.get(&name).cloned();
+                            match field {
+                                Some(value) => value,
+                                None => {
+                                    let class = Rc::clone(&instance.borrow().class);
+                                    bind_method(&class, object.clone(), &name, span)?
+                                }
+                            }
+                        }
+                        _ => {
+                            return Err(GloxError::runtime(
+                                "Only instances have properties.",
+                                span,
+                            ));
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty => {
+                    let name = read_name!();
+                    let value = self.pop();
+                    let object = self.pop();
+                    let Value::VmInstance(instance) = object else {
+                        return Err(GloxError::runtime(
+                            "Only instances have fields.",
+                            chunk.span(start, 0),
+                        ));
+                    };
+                    instance.borrow_mut().fields.insert(name, value.clone());
+                    self.stack.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = read_name!();
+                    let Value::VmClass(superclass) = self.pop() else {
+                        unreachable!("super is always a class");
+                    };
+                    let this = self.pop();
+                    let method = bind_method(&superclass, this, &name, chunk.span(start, 0))?;
+                    self.stack.push(method);
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(left.is_equal(&right)));
+                }
+                OpCode::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(!left.is_equal(&right)));
+                }
+                OpCode::Greater => binary_op!(TokenType::Greater),
+                OpCode::GreaterEqual => binary_op!(TokenType::GreaterEqual),
+                OpCode::Less => binary_op!(TokenType::Less),
+                OpCode::LessEqual => binary_op!(TokenType::LessEqual),
+                OpCode::Add => binary_op!(TokenType::Plus),
+                OpCode::Subtract => binary_op!(TokenType::Minus),
+                OpCode::Multiply => binary_op!(TokenType::Star),
+                OpCode::Divide => binary_op!(TokenType::Slash),
+                OpCode::IntegerDivide => binary_op!(TokenType::TildeSlash),
+                OpCode::Modulo => binary_op!(TokenType::Percent),
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(!value.is_truthy()));
+                }
+                OpCode::Negate => {
+                    let value = self.pop();
+                    self.stack.push(negate(value, chunk.span(start, 0))?);
+                }
+                OpCode::Print => println!("{}", self.pop()),
+                OpCode::Jump => {
+                    let offset = read_u16!() as usize;
+                    ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = read_u16!() as usize;
+                    if !self.peek(0).is_truthy() {
+                        ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = read_u16!() as usize;
+                    ip -= offset;
+                }
+                OpCode::Call => {
+                    let arguments = read_byte!() as usize;
+                    let callee = self.peek(arguments).clone();
+                    switch_frame!();
+                    self.call_value(
+                        callee,
+                        arguments,
+                        chunk.span(start, 0),
+                        chunk.span(start, 1),
+                    )?;
+                    load_frame!();
+                }
+                OpCode::GetMethod => {
+                    let name = read_name!();
+                    let span = chunk.span(start, 0);
+                    let Value::VmInstance(instance) = self.peek(0) else {
+                        return Err(GloxError::runtime("Only instances have properties.", span));
+                    };
+                    // A field holding a function is called like it would be with a
+                    // plain call
+                    let field = instance.borrow().fields.get(&name).cloned();
+                    let method = match field {
+                        Some(value) => value,
+                        None => Value::Closure(find_method(&instance.borrow().class, &name, span)?),
+                    };
+                    self.stack.push(method);
+                }
+                OpCode::GetSuperMethod => {
+                    let name = read_name!();
+                    let Value::VmClass(superclass) = self.pop() else {
+                        unreachable!("super is always a class");
+                    };
+                    let method = find_method(&superclass, &name, chunk.span(start, 0))?;
+                    self.stack.push(Value::Closure(method));
+                }
+                OpCode::Invoke => {
+                    let arguments = read_byte!() as usize;
+                    // The receiver takes the method's place below the arguments
+                    let callee = self.stack.remove(self.stack.len() - arguments - 1);
+                    switch_frame!();
+                    self.call_value(
+                        callee,
+                        arguments,
+                        chunk.span(start, 0),
+                        chunk.span(start, 1),
+                    )?;
+                    load_frame!();
+                }
+                OpCode::Closure => {
+                    let function = Rc::clone(&chunk.functions[read_u16!() as usize]);
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = read_byte!() == 1;
+                        let index = read_byte!() as usize;
+                        if is_local {
+                            upvalues.push(self.capture_upvalue(slots + index));
+                        } else {
+                            upvalues.push(Rc::clone(&closure.upvalues[index]));
+                        }
+                    }
+                    let closure = Closure { function, upvalues };
+                    self.stack.push(Value::Closure(Rc::new(closure)));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    self.close_upvalues(slots);
+                    self.frames.pop();
+                    self.stack.truncate(slots);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.stack.push(result);
+                    load_frame!();
+                }
+                OpCode::Class => {
+                    let name = read_name!();
+                    let class = VmClass {
+                        name,
+                        methods: RefCell::new(HashMap::new()),
+                    };
+                    self.stack.push(Value::VmClass(Rc::new(class)));
+                }
+                OpCode::Inherit => {
+                    let Value::VmClass(subclass) = self.pop() else {
+                        unreachable!("only classes inherit");
+                    };
+                    let Value::VmClass(superclass) = self.peek(0) else {
+                        return Err(GloxError::runtime(
+                            "Superclass must be a class.",
+                            chunk.span(start, 0),
+                        ));
+                    };
+                    // Copied down now so looking a method up never has to walk the
+                    // chain. Methods the subclass defines are added after this.
+                    let methods = superclass.methods.borrow().clone();
+                    subclass.methods.borrow_mut().extend(methods);
+                }
+                OpCode::Method => {
+                    let name = read_name!();
+                    let Value::Closure(method) = self.pop() else {
+                        unreachable!("methods are always closures");
+                    };
+                    let Value::VmClass(class) = self.peek(0) else {
+                        unreachable!("methods are only defined on classes");
+                    };
+                    class.methods.borrow_mut().insert(name, method);
+                }
+                OpCode::List => {
+                    let count = read_u16!() as usize;
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.stack
+                        .push(Value::List(Rc::new(RefCell::new(elements))));
+                }
+                OpCode::Map => {
+                    let count = read_u16!() as usize;
+                    let entries = self.stack.split_off(self.stack.len() - count * 2);
+                    let mut map = LoxMap::new();
+                    for (i, pair) in entries.chunks(2).enumerate() {
+                        map.insert(pair[0].clone(), pair[1].clone())
+                            .map_err(|message| {
+                                GloxError::runtime(message, chunk.span(start, i + 1))
+                            })?;
+                    }
+                    self.stack.push(Value::Map(Rc::new(RefCell::new(map))));
+                }
+                OpCode::Index => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    let value =
+                        get_index(object, index, chunk.span(start, 1), chunk.span(start, 0))?;
+                    self.stack.push(value);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    set_index(
+                        object,
+                        index,
+                        value.clone(),
+                        chunk.span(start, 1),
+                        chunk.span(start, 0),
+                    )?;
+                    self.stack.push(value);
+                }
+                OpCode::Interpolate => {
+                    let count = read_u16!() as usize;
+                    let parts = self.stack.split_off(self.stack.len() - count);
+                    let string: String = parts.iter().map(Value::to_string).collect();
+                    self.stack.push(Value::Str(Rc::from(string)));
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("the compiler keeps the stack balanced")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    // Calls the value with the arguments on top of the stack. For Lox functions
+    // this only pushes a frame, which run() then switches to.
+    fn call_value(
+        &mut self,
+        callee: Value,
+        arguments: usize,
+        call_span: Span,
+        callee_span: Span,
+    ) -> Result<(), GloxError> {
+        match callee {
+            Value::Closure(closure) => self.call(closure, arguments, call_span),
+            Value::BoundMethod(bound) => {
+                let slot = self.stack.len() - arguments - 1;
+                self.stack[slot] = bound.receiver.clone();
+                self.call(Rc::clone(&bound.method), arguments, call_span)
+            }
+            Value::VmClass(class) => {
+                let slot = self.stack.len() - arguments - 1;
+                let instance = VmInstance {
+                    class: Rc::clone(&class),
+                    fields: HashMap::new(),
+                };
+                self.stack[slot] = Value::VmInstance(Rc::new(RefCell::new(instance)));
+                let initializer = class.methods.borrow().get("init").cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, arguments, call_span),
+                    None if arguments != 0 => Err(wrong_arity(0, arguments, call_span)),
+                    None => Ok(()),
+                }
+            }
+            Value::Native(native) => self.call_native(&native, arguments, call_span),
+            _ => Err(GloxError::runtime(
+                "Can only call functions and classes.",
+                callee_span,
+            )),
+        }
+    }
+
+    fn call(
+        &mut self,
+        closure: Rc<Closure>,
+        arguments: usize,
+        span: Span,
+    ) -> Result<(), GloxError> {
+        if arguments != closure.function.arity {
+            return Err(wrong_arity(closure.function.arity, arguments, span));
+        }
+        if self.frames.len() == MAX_CALL_DEPTH {
+            return Err(stack_overflow(span));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - arguments - 1,
+        });
+        Ok(())
+    }
+
+    fn call_native(
+        &mut self,
+        native: &NativeFunction,
+        arguments: usize,
+        span: Span,
+    ) -> Result<(), GloxError> {
+        if arguments != native.arity() {
+            return Err(wrong_arity(native.arity(), arguments, span));
+        }
+        let values = self.stack.split_off(self.stack.len() - arguments);
+        self.pop();
+        let result = native.call_with(&values).map_err(|e| e.or_span(span))?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let position = self
+            .open_upvalues
+            .partition_point(|upvalue| open_slot(upvalue) < slot);
+        if let Some(upvalue) = self.open_upvalues.get(position)
+            && open_slot(upvalue) == slot
+        {
+            return Rc::clone(upvalue);
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.insert(position, Rc::clone(&upvalue));
+        upvalue
+    }
+
+    // Moves every captured variable at or above the slot off the stack
+    fn close_upvalues(&mut self, last: usize) {
+        let position = self
+            .open_upvalues
+            .partition_point(|upvalue| open_slot(upvalue) < last);
+        for upvalue in self.open_upvalues.drain(position..) {
+            let slot = open_slot(&upvalue);
+            *upvalue.borrow_mut() = Upvalue::Closed(self.stack[slot].clone());
+        }
+    }
+}
+
+fn open_slot(upvalue: &RefCell<Upvalue>) -> usize {
+    match &*upvalue.borrow() {
+        Upvalue::Open(slot) => *slot,
+        Upvalue::Closed(_) => unreachable!("closed upvalues aren't in the open list"),
+    }
+}
+
+fn bind_method(
+    class: &VmClass,
+    receiver: Value,
+    name: &Rc<str>,
+    span: Span,
+) -> Result<Value, GloxError> {
+    let method = find_method(class, name, span)?;
+    Ok(Value::BoundMethod(Rc::new(BoundMethod {
+        receiver,
+        method,
+    })))
+}
+
+fn find_method(class: &VmClass, name: &Rc<str>, span: Span) -> Result<Rc<Closure>, GloxError> {
+    match class.methods.borrow().get(name) {
+        Some(method) => Ok(Rc::clone(method)),
+        None => Err(undefined_property(name, span)),
+    }
+}
+
+// The same errors the tree-walker gives
+
+fn undefined_variable(name: &str, span: Span) -> GloxError {
+    GloxError::runtime(format!("Undefined variable '{}'.", name), span)
+        .with_help(format!("declare it first with `var {} = ...;`", name))
+}
+
+fn undefined_property(name: &str, span: Span) -> GloxError {
+    GloxError::runtime(format!("Undefined property '{}'.", name), span)
+}
+
+fn wrong_arity(arity: usize, arguments: usize, span: Span) -> GloxError {
+    GloxError::runtime(
+        format!("Expected {} arguments but got {}.", arity, arguments),
+        span,
+    )
+}
+
+// These can refer back to themselves through what they hold, so none of them
+// derive Debug
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+impl fmt::Display for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+impl fmt::Debug for BoundMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.method)
+    }
+}
+
+impl fmt::Display for BoundMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.method)
+    }
+}
+
+impl fmt::Debug for VmClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Display for VmClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Debug for VmInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+impl fmt::Display for VmInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,344 @@
+// The bytecode VM against the tree-walker. Programs are run through the glox
+// binary both ways and have to agree on everything a user can see.
+use std::process::Command;
+
+use glox::{Backend, Glox, GloxError};
+
+// What running the program printed to stdout and stderr, and the exit code
+fn run(source: &str, backend: Backend) -> (String, String, Option<i32>) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_glox"));
+    if backend == Backend::TreeWalker {
+        command.arg("--tree-walker");
+    }
+    let output = command.args(["-e", source]).output().unwrap();
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+        output.status.code(),
+    )
+}
+
+// Checks both backends give the same result and returns what the program printed,
+// with anything it printed to stderr after it
+fn both(source: &str) -> String {
+    let vm = run(source, Backend::Vm);
+    let tree_walker = run(source, Backend::TreeWalker);
+    assert_eq!(vm, tree_walker, "backends disagree on {}", source);
+    vm.0 + &vm.1
+}
+
+#[test]
+fn arithmetic_and_control_flow() {
+    let source = "
+        var total = 0;
+        for (var i = 1; i <= 10; i = i + 1) {
+            if (i % 2 == 0) total = total + i; else total = total - 1;
+        }
+        print total;
+        var n = 0;
+        while (n < 3) n = n + 1;
+        print n;
+        print 7 / 2;
+        print 7 ~/ 2;
+        print -7 % 3;
+        print 0 / 0 < 1;
+        print 0 / 0 >= 1;
+        print nil or \"default\";
+        print 1 and false;
+        print !nil;
+    ";
+    assert_eq!(
+        both(source),
+        "25\n3\n3.5\n3\n2\nfalse\nfalse\ndefault\nfalse\ntrue\n"
+    );
+}
+
+#[test]
+fn closures_capture_variables() {
+    let source = "
+        fun makeCounter() {
+            var count = 0;
+            fun increment() {
+                count = count + 1;
+                return count;
+            }
+            return increment;
+        }
+        var a = makeCounter();
+        var b = makeCounter();
+        print a();
+        print a();
+        print b();
+        fun constant(x) {
+            fun f() { return x; }
+            return f;
+        }
+        var fns = [];
+        for (var i = 0; i < 3; i = i + 1) {
+            var j = i;
+            push(fns, constant(j));
+        }
+        print fns[0]() + fns[2]();
+        {
+            var shared = 1;
+            fun get() { return shared; }
+            fun set(v) { shared = v; }
+            set(5);
+            print get();
+        }
+        print makeCounter;
+    ";
+    assert_eq!(both(source), "1\n2\n1\n2\n5\n<fn makeCounter>\n");
+}
+
+// A for loop behaves like `{ init; while (cond) { body; incr; } }`
+#[test]
+fn for_loops_run_like_while_loops() {
+    let source = "
+        var fns = [];
+        for (var i = 0; i < 3; i = i + 1) {
+            fun f() { return i; }
+            push(fns, f);
+        }
+        print fns[0]();
+        var i = \"outer\";
+        for (var i = 0; i < 1; i = i + 1) {}
+        print i;
+        var n = 0;
+        for (; n < 2;) n = n + 1;
+        print n;
+        fun first() {
+            for (var k = 10;; k = k + 1) if (k % 7 == 0) return k;
+        }
+        print first();
+    ";
+    // Every iteration shares the one i, which ends up at 3
+    assert_eq!(both(source), "3\nouter\n2\n14\n");
+}
+
+#[test]
+fn classes_and_inheritance() {
+    let source = "
+        class Animal {
+            init(name) {
+                this.name = name;
+                return;
+            }
+            speak() { return this.name + \" makes a sound\"; }
+            describe() { return \"I am \" + this.name; }
+        }
+        class Dog < Animal {
+            init(name) {
+                super.init(name);
+                this.tricks = [];
+            }
+            speak() { return super.speak() + \", woof\"; }
+        }
+        var d = Dog(\"Rex\");
+        print d.speak();
+        print d.describe();
+        var method = d.speak;
+        print method();
+        fun describer(x) { return x.describe; }
+        print describer(d)();
+        d.fn = clock;
+        print d.fn() > 0;
+        print d.init(\"Max\").name;
+        print d;
+        print Dog;
+        print d == d;
+        print Dog(\"a\") == Dog(\"a\");
+    ";
+    assert_eq!(
+        both(source),
+        "Rex makes a sound, woof\nI am Rex\nRex makes a sound, woof\nI am Rex\ntrue\nMax\n\
+         Dog instance\nDog\ntrue\nfalse\n"
+    );
+}
+
+#[test]
+fn lists_maps_and_interpolation() {
+    let source = "
+        var xs = [3, 1, 2];
+        sort(xs);
+        xs[0] = xs[-1] * 10;
+        var m = {\"a\": xs, 1: {}};
+        m[1][\"b\"] = nil;
+        m[1.0][\"c\"] = true;
+        print m;
+        print len(m[\"a\"]);
+        var name = \"world\";
+        print \"hello ${name}, ${1 + 1} ${[name]}\";
+    ";
+    assert_eq!(
+        both(source),
+        "{\"a\": [30, 2, 3], 1: {\"b\": nil, \"c\": true}}\n3\nhello world, 2 [\"world\"]\n"
+    );
+}
+
+#[test]
+fn runtime_errors_match() {
+    let programs = [
+        "print undefined;",
+        "undefined = 1;",
+        "var a = 1; a();",
+        "fun f(a) {} f(1, 2);",
+        "clock(1);",
+        "print len(1);",
+        "var a = 1; print a.b;",
+        "var a = 1; a.b = 2;",
+        "class A {} print A().x;",
+        "class A {} A().x();",
+        "class A {} A(1);",
+        "class A { init() { this.f = 1; } } A().f();",
+        "var a = 1; class B < a {}",
+        "class A {} class B < A { m() { super.m(); } } B().m();",
+        "class A {} class B < A { m() { return super.m; } } B().m();",
+        "print 1 + \"a\";",
+        "print -\"a\";",
+        "print \"a\" < 1;",
+        "print 1 ~/ 0;",
+        "print 9223372036854775807 + 1;",
+        "print [1][5];",
+        "var xs = [1]; xs[1.5] = 0;",
+        "print {[1]: 2};",
+        "var m = {}; m[0 / 0] = 1;",
+        "print {}[\"missing\"];",
+        "print \"before\";\nprint nil + 1;",
+    ];
+    for source in programs {
+        let output = both(source);
+        assert!(output.contains("RuntimeError"), "{} didn't fail", source);
+    }
+}
+
+// The method is looked up before the arguments run, like the callee of any call
+#[test]
+fn method_errors_come_before_the_arguments_run() {
+    let setup = "fun f() { print \"side effect\"; return 1; }\n";
+    let programs = [
+        "var x = nil; x.foo(f());",
+        "class A {} A().missing(f());",
+        "class A {} class B < A { m() { super.missing(f()); } } B().m();",
+    ];
+    for program in programs {
+        let source = format!("{}{}", setup, program);
+        let output = both(&source);
+        assert!(output.starts_with("RuntimeError"), "{}", output);
+    }
+    // A field is read before the arguments could reassign it
+    let source = "
+        class A {}
+        var a = A();
+        fun old(x) { return \"old\"; }
+        fun new(x) { return \"new\"; }
+        fun reassign() { a.f = new; return 0; }
+        a.f = old;
+        print a.f(reassign());
+        print a.f(0);
+    ";
+    assert_eq!(both(source), "old\nnew\n");
+}
+
+#[test]
+fn eval_and_globals_match() {
+    for backend in [Backend::Vm, Backend::TreeWalker] {
+        let mut glox = Glox::with_backend(backend);
+        glox.run("var x = 2; fun double(n) { return n * 2; }")
+            .unwrap();
+        assert_eq!(glox.eval("double(x) + 1").unwrap().to_string(), "5");
+        assert_eq!(glox.get_global("x").unwrap().to_string(), "2");
+        assert!(glox.run("x = x + 1; nil();").is_err());
+        // Globals set before the error stay set
+        assert_eq!(glox.eval("x").unwrap().to_string(), "3");
+    }
+}
+
+#[test]
+fn deep_recursion_is_an_error_on_the_vm() {
+    let errors = Glox::new().run("fun f() { f(); } f();").unwrap_err();
+    let diagnostic = errors[0].diagnostic();
+    assert_eq!(diagnostic.message, "Stack overflow.");
+    assert!(
+        Glox::new()
+            .run("fun f(n) { if (n > 0) f(n - 1); } f(5000);")
+            .is_ok()
+    );
+}
+
+#[test]
+fn deep_recursion_is_an_error_on_the_tree_walker() {
+    // Test threads have a small stack, which the tree-walker grows as it needs to
+    let mut glox = Glox::with_backend(Backend::TreeWalker);
+    let errors = glox.run("fun f() { f(); } f();").unwrap_err();
+    let diagnostic = errors[0].diagnostic();
+    assert_eq!(diagnostic.message, "Stack overflow.");
+    assert!(
+        glox.run("fun g(n) { if (n > 0) g(n - 1); } g(5000);")
+            .is_ok()
+    );
+    // Both backends stop at the same depth
+    let output = both(
+        "fun f(n) { if (n > 0) return f(n - 1) + 1; return 0; }
+         print f(9998);
+         print f(9999);",
+    );
+    assert!(output.starts_with("9998\nRuntimeError: Stack overflow."));
+}
+
+#[test]
+fn programs_too_big_for_the_bytecode() {
+    let elements: Vec<String> = (0..70_000).map(|i| i.to_string()).collect();
+    let source = format!("var xs = [{}];", elements.join(", "));
+    let errors = Glox::new().run(&source).unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[0], GloxError::CompileError(_)));
+    assert_eq!(
+        errors[0].diagnostic().message,
+        "Too many constants in one chunk."
+    );
+    assert_eq!(
+        errors[1].diagnostic().message,
+        "Too many elements in a list."
+    );
+    assert_eq!(Glox::disassemble(&source).unwrap_err().len(), 2);
+    // The tree-walker has no such limits, and check doesn't compile
+    assert!(Glox::with_backend(Backend::TreeWalker).run(&source).is_ok());
+    assert!(Glox::check(&source).is_ok());
+}
+
+#[test]
+fn disassembles_every_function() {
+    let output = Command::new(env!("CARGO_BIN_EXE_glox"))
+        .args([
+            "disasm",
+            "-e",
+            "fun add(a, b) {\n  return a + b;\n}\nprint add(1, 2);",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "\
+== <script> ==
+0000    1 OP_CLOSURE          0 <fn add>
+0003    | OP_DEFINE_GLOBAL    0 \"add\"
+0006    4 OP_GET_GLOBAL       0 \"add\"
+0009    | OP_CONSTANT         1 1
+0012    | OP_CONSTANT         2 2
+0015    | OP_CALL             2
+0017    | OP_PRINT
+0018    | OP_NIL
+0019    | OP_RETURN
+
+== <fn add> ==
+0000    2 OP_GET_LOCAL        1
+0002    | OP_GET_LOCAL        2
+0004    | OP_ADD
+0005    | OP_RETURN
+0006    | OP_NIL
+0007    | OP_RETURN
+"
+    );
+}
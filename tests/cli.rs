@@ -0,0 +1,322 @@
+// The glox binary: its commands, the ways of giving it a script and its exit codes
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// What running glox with the arguments printed to stdout and stderr, and the
+// exit code. The input is piped to its stdin.
+fn glox(args: &[&str], input: &str) -> (String, String, Option<i32>) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_glox"));
+    command.args(args);
+    run(&mut command, input)
+}
+
+// What a prompt session fed the input printed to stdout and stderr. Without a
+// terminal the prompts themselves aren't printed.
+fn repl(input: &str) -> (String, String) {
+    // Keeps the history file out of the real home directory
+    let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("home");
+    fs::create_dir_all(&home).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_glox"));
+    command.arg("repl").env("HOME", home);
+    let (stdout, stderr, code) = run(&mut command, input);
+    assert_eq!(code, Some(0), "{}", stderr);
+    (stdout, stderr)
+}
+
+fn run(command: &mut Command, input: &str) -> (String, String, Option<i32>) {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+        output.status.code(),
+    )
+}
+
+// A script written out to a file for the test to pass by path
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn runs_a_file_inline_code_or_stdin() {
+    let path = script("hello.lox", "print \"hello\";");
+    let (stdout, _, code) = glox(&[path.to_str().unwrap()], "");
+    assert_eq!((stdout.as_str(), code), ("hello\n", Some(0)));
+    let (stdout, _, code) = glox(&["run", path.to_str().unwrap()], "");
+    assert_eq!((stdout.as_str(), code), ("hello\n", Some(0)));
+
+    let (stdout, _, code) = glox(&["-e", "print 1 + 2;"], "");
+    assert_eq!((stdout.as_str(), code), ("3\n", Some(0)));
+
+    let (stdout, _, code) = glox(&["-"], "var a = 2;\nprint a * a;\n");
+    assert_eq!((stdout.as_str(), code), ("4\n", Some(0)));
+    let (stdout, _, code) = glox(&["--tree-walker", "-"], "print 5;");
+    assert_eq!((stdout.as_str(), code), ("5\n", Some(0)));
+}
+
+#[test]
+fn check_reports_errors_without_running() {
+    let (stdout, stderr, code) = glox(&["check", "-e", "print 1;"], "");
+    assert_eq!((stdout.as_str(), stderr.as_str(), code), ("", "", Some(0)));
+    let (stdout, _, code) = glox(&["check", "-e", "print undefined;"], "");
+    assert_eq!((stdout.as_str(), code), ("", Some(0)));
+
+    let (_, stderr, code) = glox(&["check", "-e", "return 1;"], "");
+    assert!(stderr.starts_with("Resolution error: Can't return from top-level code."));
+    assert_eq!(code, Some(65));
+
+    // Only the VM can't fit this in its bytecode, and check doesn't compile
+    let elements: Vec<String> = (0..70_000).map(|i| i.to_string()).collect();
+    let source = format!("var xs = [{}];", elements.join(", "));
+    for args in [&["check", "-"][..], &["--tree-walker", "check", "-"]] {
+        let (_, stderr, code) = glox(args, &source);
+        assert_eq!((stderr.as_str(), code), ("", Some(0)), "{:?}", args);
+    }
+    let (_, stderr, code) = glox(&["-"], &source);
+    assert!(
+        stderr.starts_with("Compile error: Too many constants"),
+        "{}",
+        stderr
+    );
+    assert_eq!(code, Some(65));
+}
+
+#[test]
+fn tokens_and_syntax_tree() {
+    let (stdout, _, code) = glox(&["tokens", "-e", "print 1;"], "");
+    assert_eq!(code, Some(0));
+    let lines: Vec<Vec<&str>> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            vec!["1:1", "Print", "print"],
+            vec!["1:7", "Number", "1", "1"],
+            vec!["1:8", "Semicolon", ";"],
+            vec!["1:9", "EOF"],
+        ]
+    );
+
+    let (stdout, _, code) = glox(&["ast", "-e", "print 1 + 2 * 3;"], "");
+    assert_eq!(
+        (stdout.as_str(), code),
+        ("(print (+ 1 (* 2 3)))\n", Some(0))
+    );
+    let (stdout, _, _) = glox(&["ast", "-"], "-a.b");
+    assert_eq!(stdout, "(- (. b a))\n");
+}
+
+#[test]
+fn formats_a_script() {
+    let (stdout, _, code) = glox(
+        &["fmt", "-"],
+        "if(x){print \"${x}\";}else print 2; fun f(a,b){return a;}",
+    );
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout,
+        "if (x) {\n  print \"${x}\";\n} else\n  print 2;\n\nfun f(a, b) {\n  return a;\n}\n"
+    );
+    // Formatting is stable
+    let (again, _, _) = glox(&["fmt", "-"], &stdout);
+    assert_eq!(again, stdout);
+}
+
+#[test]
+fn refuses_to_format_comments_away() {
+    let source = "var a = 1; // one\nprint a;\n";
+    let (stdout, stderr, code) = glox(&["fmt", "-"], source);
+    assert_eq!(stdout, "");
+    assert!(stderr.starts_with("Parsing error: Can't format a script with comments in it."));
+    assert!(stderr.contains("--> <stdin>:1:12"));
+    assert_eq!(code, Some(65));
+    // Only real comments count
+    let (stdout, _, code) = glox(&["fmt", "-e", "print \"//\";"], "");
+    assert_eq!((stdout.as_str(), code), ("print \"//\";\n", Some(0)));
+}
+
+#[test]
+fn help() {
+    let (stdout, _, code) = glox(&["--help"], "");
+    assert!(stdout.starts_with("Usage: glox"));
+    assert_eq!(code, Some(0));
+}
+
+#[test]
+fn bad_usage_exits_with_64() {
+    for args in [
+        &["-e"][..],
+        &["run", "a.lox", "b.lox"],
+        &["repl", "a.lox"],
+        &["check", "--verbose"],
+    ] {
+        let (stdout, stderr, code) = glox(args, "");
+        assert_eq!(stdout, "", "{:?}", args);
+        assert!(stderr.starts_with("Usage: glox"), "{:?}", args);
+        assert_eq!(code, Some(64), "{:?}", args);
+    }
+}
+
+#[test]
+fn syntax_errors_exit_with_65() {
+    let path = script("syntax_error.lox", "print 1;\nprint ;\nvar = 2;\n");
+    let (stdout, stderr, code) = glox(&[path.to_str().unwrap()], "");
+    // Nothing runs when any of the script fails to parse
+    assert_eq!(stdout, "");
+    assert_eq!(code, Some(65));
+    let location = format!("--> {}:2:7", path.display());
+    assert!(stderr.contains(&location), "{}", stderr);
+    assert_eq!(stderr.matches("Parsing error").count(), 2);
+}
+
+#[test]
+fn unreadable_input_exits_with_66() {
+    let (_, stderr, code) = glox(&["missing.lox"], "");
+    assert!(stderr.starts_with("Error: failed to read missing.lox"));
+    assert_eq!(code, Some(66));
+}
+
+#[test]
+fn runtime_errors_exit_with_70() {
+    for backend in [&[][..], &["--tree-walker"]] {
+        let args = [backend, &["-e", "print 1;\nprint nil + 1;\nprint 3;"]].concat();
+        let (stdout, stderr, code) = glox(&args, "");
+        // What ran before the error stays printed
+        assert_eq!(stdout, "1\n");
+        assert!(stderr.starts_with("RuntimeError: Tried to add"));
+        assert!(stderr.contains("--> <eval>:2:7"));
+        assert_eq!(code, Some(70));
+    }
+}
+
+#[test]
+fn the_prompt_prints_expressions_and_runs_statements() {
+    let (stdout, stderr) = repl("var a = 1;\na + 1\nprint a;\n\n\"${a}\" + \"!\"\n");
+    assert_eq!((stdout.as_str(), stderr.as_str()), ("2\n1\n1!\n", ""));
+    // An error doesn't end the session
+    let (stdout, stderr) = repl("print nil + 1;\nprint 2;\n");
+    assert_eq!(stdout, "2\n");
+    assert!(
+        stderr.starts_with("RuntimeError: Tried to add"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("--> <prompt>:1:7"), "{}", stderr);
+}
+
+#[test]
+fn the_prompt_waits_for_unclosed_brackets_and_strings() {
+    let input = "\
+fun f(n) {
+  print \"called\";
+  return n;
+}
+f(
+  1
+)
+[1,
+ 2]
+\"a
+b\"
+\"x${
+  1 + 1}y\"
+{
+  print 3;
+  print 4;
+}
+";
+    let (stdout, stderr) = repl(input);
+    // Each entry runs once, when it's complete
+    assert_eq!(stdout, "called\n1\n[1, 2]\na\nb\nx2y\n3\n4\n");
+    assert_eq!(stderr, "");
+    // Closing more than was opened is an error straight away
+    let (stdout, stderr) = repl(")\nprint 5;\n");
+    assert_eq!(stdout, "5\n");
+    assert!(stderr.starts_with("Parsing error"), "{}", stderr);
+}
+
+#[test]
+fn prompt_commands() {
+    let (stdout, stderr) = repl(":tokens print 1;\n:ast 1 + 2 * 3\n:disasm print 1;\n");
+    let lines: Vec<Vec<&str>> = stdout
+        .lines()
+        .take(5)
+        .map(|line| line.split_whitespace().collect())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            vec!["1:1", "Print", "print"],
+            vec!["1:7", "Number", "1", "1"],
+            vec!["1:8", "Semicolon", ";"],
+            vec!["1:9", "EOF"],
+            vec!["(+", "1", "(*", "2", "3))"],
+        ]
+    );
+    assert!(stdout.contains("== <script> ==\n"), "{}", stdout);
+    assert!(stdout.contains("OP_PRINT"), "{}", stdout);
+    assert_eq!(stderr, "");
+
+    // Errors point into the command's argument
+    let (_, stderr) = repl(":ast (\n");
+    assert!(
+        stderr.contains("--> <prompt>:1:2\n  |\n1 | (\n"),
+        "{}",
+        stderr
+    );
+
+    let (stdout, _) = repl(":help\n");
+    assert!(stdout.starts_with(":tokens <code>"), "{}", stdout);
+    assert!(stdout.contains(":reset "), "{}", stdout);
+}
+
+#[test]
+fn prompt_commands_on_the_session() {
+    let path = script(
+        "load.lox",
+        "var loaded = 2;\nfun twice(x) { return x * 2; }\n",
+    );
+    let input = format!(
+        "var a = \"x\";\n:load {}\n:env\ntwice(loaded)\n:time print twice(3);\n:reset\n:env\nprint a;\n",
+        path.display()
+    );
+    let (stdout, stderr) = repl(&input);
+    let lines: Vec<&str> = stdout.lines().collect();
+    // :env leaves out the standard library
+    assert_eq!(
+        lines[..5],
+        ["a = x", "loaded = 2", "twice = <fn twice>", "4", "6"]
+    );
+    assert!(lines[5].starts_with("Took "), "{}", stdout);
+    assert_eq!(lines[6..], ["Session reset."]);
+    // Nothing is defined after :reset
+    assert!(
+        stderr.starts_with("RuntimeError: Undefined variable 'a'."),
+        "{}",
+        stderr
+    );
+
+    let (stdout, stderr) = repl(":load missing.lox\n:bogus 1\nprint 1;\n");
+    assert_eq!(stdout, "1\n");
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert!(lines[0].starts_with("Error: failed to read missing.lox: "));
+    assert_eq!(lines[1..], ["Unknown command ':bogus', try :help"]);
+}
@@ -0,0 +1,77 @@
+// Helpers shared by the integration tests. Each test file only uses some of them.
+#![allow(dead_code)]
+
+use glox::{Backend, Glox, GloxError, Value};
+
+// Evaluates an expression in a fresh instance
+pub fn value(source: &str) -> Value {
+    eval_value("", source)
+}
+
+// Evaluates an expression in a fresh instance and returns it as it would print
+pub fn eval(source: &str) -> String {
+    value(source).to_string()
+}
+
+// Runs the setup, then evaluates an expression and returns it as it would print
+pub fn eval_with(setup: &str, source: &str) -> String {
+    eval_value(setup, source).to_string()
+}
+
+// The message of the runtime error the statements fail with
+pub fn error(source: &str) -> String {
+    match Glox::new().run(source) {
+        Ok(()) => panic!("{} should have failed", source),
+        Err(errors) => runtime_message(source, &errors),
+    }
+}
+
+// The message of the runtime error the expression fails with
+pub fn eval_error(source: &str) -> String {
+    match Glox::new().eval(source) {
+        Ok(value) => panic!("{} should have failed, got {}", source, value),
+        Err(errors) => runtime_message(source, &errors),
+    }
+}
+
+// Like eval_with on both backends, which have to agree
+pub fn eval_both(setup: &str, source: &str) -> String {
+    let vm = eval_value_on(Backend::Vm, setup, source).to_string();
+    let tree_walker = eval_value_on(Backend::TreeWalker, setup, source).to_string();
+    assert_eq!(vm, tree_walker, "backends disagree on {}", source);
+    vm
+}
+
+// Like eval_error on both backends, which have to agree
+pub fn eval_error_both(source: &str) -> String {
+    let messages = [Backend::Vm, Backend::TreeWalker].map(|backend| {
+        match Glox::with_backend(backend).eval(source) {
+            Ok(value) => panic!("{} should have failed, got {}", source, value),
+            Err(errors) => runtime_message(source, &errors),
+        }
+    });
+    assert_eq!(messages[0], messages[1], "backends disagree on {}", source);
+    messages[0].clone()
+}
+
+fn eval_value(setup: &str, source: &str) -> Value {
+    eval_value_on(Backend::Vm, setup, source)
+}
+
+fn eval_value_on(backend: Backend, setup: &str, source: &str) -> Value {
+    let mut glox = Glox::with_backend(backend);
+    if let Err(errors) = glox.run(setup) {
+        panic!("{} failed: {}", setup, errors[0]);
+    }
+    match glox.eval(source) {
+        Ok(value) => value,
+        Err(errors) => panic!("{} failed: {}", source, errors[0]),
+    }
+}
+
+fn runtime_message(source: &str, errors: &[GloxError]) -> String {
+    match &errors[0] {
+        GloxError::RuntimeError(diagnostic) => diagnostic.message.clone(),
+        e => panic!("{} failed with a non-runtime error: {}", source, e),
+    }
+}
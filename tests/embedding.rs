@@ -0,0 +1,219 @@
+// Calling Rust from Lox through Glox::register, and the conversions between
+// Lox values and Rust types that come with it, see src/native.rs
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glox::{Backend, Glox, GloxError, Value};
+
+// A fresh instance on each backend, set up by the closure
+fn both(setup: impl Fn(&mut Glox)) -> [Glox; 2] {
+    [Backend::Vm, Backend::TreeWalker].map(|backend| {
+        let mut glox = Glox::with_backend(backend);
+        setup(&mut glox);
+        glox
+    })
+}
+
+// The runtime error evaluating the expression fails with, and the source it points at
+fn error(glox: &mut Glox, source: &str) -> (String, String) {
+    let errors = glox.eval(source).unwrap_err();
+    let GloxError::RuntimeError(diagnostic) = &errors[0] else {
+        panic!("{} failed with {}", source, errors[0]);
+    };
+    let span = diagnostic.span;
+    (
+        diagnostic.message.clone(),
+        source[span.start..span.end].to_string(),
+    )
+}
+
+#[test]
+fn registered_closures_are_called_from_lox() {
+    for mut glox in both(|glox| {
+        glox.register("hypot", |x: f64, y: f64| x.hypot(y));
+        glox.register("greet", |name: String| format!("Hello, {}!", name));
+        glox.register("repeat", |s: Rc<str>, n: i64| s.repeat(n as usize));
+        glox.register("answer", || 42i64);
+        glox.register("not", |b: bool| !b);
+        glox.register("describe", |x: Value| x.type_name());
+        glox.register("nothing", || ());
+    }) {
+        assert_eq!(glox.eval("hypot(3, 4)").unwrap().to_string(), "5");
+        assert_eq!(
+            glox.eval("greet(\"Lox\")").unwrap().to_string(),
+            "Hello, Lox!"
+        );
+        assert_eq!(
+            glox.eval("repeat(\"ab\", 3)").unwrap().to_string(),
+            "ababab"
+        );
+        assert_eq!(glox.eval("answer() + 1").unwrap().to_string(), "43");
+        assert_eq!(glox.eval("not(false)").unwrap().to_string(), "true");
+        assert_eq!(glox.eval("describe([])").unwrap().to_string(), "list");
+        assert_eq!(glox.eval("nothing()").unwrap().to_string(), "nil");
+        assert_eq!(glox.eval("greet").unwrap().to_string(), "<native fn greet>");
+        // They're values like any other function
+        glox.run("var f = hypot; fun call(g) { return g(6, 8); }")
+            .unwrap();
+        assert_eq!(glox.eval("call(f)").unwrap().to_string(), "10");
+    }
+}
+
+#[test]
+fn closures_can_capture_host_state() {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let mut glox = Glox::new();
+    let log = Rc::clone(&calls);
+    glox.register("log", move |message: String| {
+        log.borrow_mut().push(message);
+    });
+    glox.run("for (var i = 0; i < 3; i = i + 1) log(\"call ${i}\");")
+        .unwrap();
+    assert_eq!(*calls.borrow(), ["call 0", "call 1", "call 2"]);
+}
+
+#[test]
+fn lists_are_passed_by_reference() {
+    for mut glox in both(|glox| {
+        glox.register("push_twice", |list: Rc<RefCell<Vec<Value>>>, x: Value| {
+            list.borrow_mut().extend([x.clone(), x]);
+        });
+        glox.register("range", |n: i64| {
+            (0..n).map(Value::Int).collect::<Vec<Value>>()
+        });
+    }) {
+        glox.run("var xs = [1]; push_twice(xs, 2);").unwrap();
+        assert_eq!(glox.eval("xs").unwrap().to_string(), "[1, 2, 2]");
+        assert_eq!(glox.eval("range(3)").unwrap().to_string(), "[0, 1, 2]");
+    }
+}
+
+#[test]
+fn optional_arguments_and_results() {
+    for mut glox in both(|glox| {
+        glox.register("or_zero", |x: Option<i64>| x.unwrap_or(0));
+        glox.register("half", |x: i64| if x % 2 == 0 { Some(x / 2) } else { None });
+    }) {
+        assert_eq!(glox.eval("or_zero(nil)").unwrap().to_string(), "0");
+        assert_eq!(glox.eval("or_zero(5)").unwrap().to_string(), "5");
+        assert_eq!(glox.eval("half(4)").unwrap().to_string(), "2");
+        assert_eq!(glox.eval("half(3)").unwrap().to_string(), "nil");
+        assert_eq!(
+            error(&mut glox, "or_zero(\"a\")").0,
+            "Expected an integer for argument 1 of 'or_zero' but got string."
+        );
+    }
+}
+
+#[test]
+fn err_results_are_runtime_errors() {
+    for mut glox in both(|glox| {
+        glox.register("parse", |s: String| {
+            s.parse::<i64>().map_err(|e| e.to_string())
+        });
+    }) {
+        assert_eq!(glox.eval("parse(\"12\")").unwrap().to_string(), "12");
+        assert_eq!(
+            error(&mut glox, "1 + parse(\"x\")"),
+            (
+                "invalid digit found in string".to_string(),
+                "parse(\"x\")".to_string()
+            )
+        );
+    }
+}
+
+#[test]
+fn the_arity_comes_from_the_closure() {
+    for mut glox in both(|glox| {
+        glox.register("add", |a: i64, b: i64| a + b);
+        glox.register("five", |a: i64, b: i64, c: i64, d: i64, e: i64| {
+            a + b + c + d + e
+        });
+    }) {
+        assert_eq!(
+            error(&mut glox, "add(1)"),
+            (
+                "Expected 2 arguments but got 1.".to_string(),
+                "add(1)".to_string()
+            )
+        );
+        assert_eq!(
+            error(&mut glox, "add(1, 2, 3)").0,
+            "Expected 2 arguments but got 3."
+        );
+        assert_eq!(glox.eval("five(1, 2, 3, 4, 5)").unwrap().to_string(), "15");
+    }
+}
+
+#[test]
+fn arguments_of_the_wrong_type_are_runtime_errors() {
+    for mut glox in both(|glox| {
+        glox.register("hypot", |x: f64, y: f64| x.hypot(y));
+        glox.register("not", |b: bool| !b);
+        glox.register("greet", |name: String| name);
+    }) {
+        assert_eq!(
+            error(&mut glox, "hypot(1, \"2\")"),
+            (
+                "Expected a number for argument 2 of 'hypot' but got string.".to_string(),
+                "hypot(1, \"2\")".to_string()
+            )
+        );
+        // Truthy isn't enough
+        assert_eq!(
+            error(&mut glox, "not(nil)").0,
+            "Expected a bool for argument 1 of 'not' but got nil."
+        );
+        assert_eq!(
+            error(&mut glox, "greet(1)").0,
+            "Expected a string for argument 1 of 'greet' but got int."
+        );
+    }
+}
+
+#[test]
+fn floats_convert_to_i64_only_when_whole_and_in_range() {
+    for mut glox in both(|glox| {
+        glox.register("id", |n: i64| n);
+    }) {
+        assert!(matches!(glox.eval("id(2.0)").unwrap(), Value::Int(2)));
+        assert!(matches!(glox.eval("id(-0.0)").unwrap(), Value::Int(0)));
+        // -2^63 is i64::MIN, 2^63 is one past i64::MAX
+        assert!(matches!(
+            glox.eval("id(-9223372036854775808.0)").unwrap(),
+            Value::Int(i64::MIN)
+        ));
+        for source in [
+            "id(2.5)",
+            "id(9223372036854775808.0)",
+            "id(1e19)",
+            "id(-1e19)",
+            "id(1 / 0)",
+            "id(-1 / 0)",
+            "id(0 / 0)",
+        ] {
+            assert_eq!(
+                error(&mut glox, source).0,
+                "Expected an integer for argument 1 of 'id' but got float.",
+                "{}",
+                source
+            );
+        }
+    }
+}
+
+#[test]
+fn define_native_takes_the_arguments_as_values() {
+    let mut glox = Glox::new();
+    glox.define_native("count", 2, |arguments| {
+        Ok(Value::Int(
+            arguments.iter().filter(|x| x.is_truthy()).count() as i64,
+        ))
+    });
+    assert_eq!(glox.eval("count(nil, 0)").unwrap().to_string(), "1");
+    assert_eq!(
+        error(&mut glox, "count()").0,
+        "Expected 2 arguments but got 0."
+    );
+}
@@ -0,0 +1,167 @@
+// How errors are reported: their spans, the rendered source excerpt and help,
+// and every error in a script coming out at once. See GloxError::render.
+use std::process::Command;
+
+use glox::{Glox, GloxError};
+
+// Every error check finds in the source, rendered as the glox binary prints them
+fn rendered(source: &str) -> String {
+    let errors = Glox::check(source).unwrap_err();
+    errors
+        .iter()
+        .map(|e| e.render(source, "test.lox"))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[test]
+fn syntax_errors() {
+    assert_eq!(
+        rendered("var a = 1;\nprint a +;\n"),
+        "\
+Parsing error: Expect expression, found ';'.
+  --> test.lox:2:10
+  |
+2 | print a +;
+  |          ^
+"
+    );
+}
+
+#[test]
+fn a_missing_token_points_past_the_previous_one() {
+    assert_eq!(
+        rendered("print 1\nprint 2;"),
+        "\
+Parsing error: Expect ';' after value.
+  --> test.lox:1:8
+  |
+1 | print 1
+  |        ^
+  = help: add a ';' to end the statement
+"
+    );
+}
+
+#[test]
+fn a_missing_token_after_multibyte_characters() {
+    assert_eq!(
+        rendered("var x = \"é\"\nprint x;"),
+        "\
+Parsing error: Expect ';' after variable declaration.
+  --> test.lox:1:12
+  |
+1 | var x = \"é\"
+  |            ^
+  = help: add a ';' to end the statement
+"
+    );
+    // Past the end of a string that runs over lines
+    let errors = Glox::check("print \"\"\"a\nbé\"\"\"\nprint 1;").unwrap_err();
+    let span = errors[0].diagnostic().span;
+    assert_eq!((span.line, span.column), (2, 6));
+}
+
+#[test]
+fn resolver_errors() {
+    assert_eq!(
+        rendered("fun f() {\n  var x = 1;\n  var x = 2;\n}\nreturn 3;"),
+        "\
+Resolution error: Already a variable with this name in this scope.
+  --> test.lox:3:7
+  |
+3 |   var x = 2;
+  |       ^
+
+Resolution error: Can't return from top-level code.
+  --> test.lox:5:1
+  |
+5 | return 3;
+  | ^^^^^^
+  = help: 'return' can only be used inside a function or method
+"
+    );
+}
+
+#[test]
+fn every_syntax_error_is_reported() {
+    let source = "print ;\nvar = 1;\nprint (1;\nprint 4;";
+    let errors = Glox::check(source).unwrap_err();
+    let locations: Vec<(usize, usize)> = errors
+        .iter()
+        .map(|e| {
+            assert!(matches!(e, GloxError::UnexpectedToken(_)), "{}", e);
+            let span = e.diagnostic().span;
+            (span.line, span.column)
+        })
+        .collect();
+    assert_eq!(locations, [(1, 7), (2, 5), (3, 9)]);
+}
+
+#[test]
+fn spans_cover_the_whole_expression() {
+    let source = "var a = \"x\";\nprint a - (1 + 2);";
+    let errors = Glox::new().run(source).unwrap_err();
+    let span = errors[0].diagnostic().span;
+    assert_eq!(&source[span.start..span.end], "a - (1 + 2)");
+    assert_eq!(
+        errors[0].render(source, "test.lox"),
+        "\
+RuntimeError: Tried to subtract something other than two numbers
+  --> test.lox:2:7
+  |
+2 | print a - (1 + 2);
+  |       ^^^^^^^^^^^
+"
+    );
+}
+
+#[test]
+fn multiline_spans_underline_their_first_line() {
+    let source = "print \"a\" -\n  1;";
+    let rendered = Glox::new().run(source).unwrap_err()[0].render(source, "test.lox");
+    assert!(
+        rendered.ends_with("1 | print \"a\" -\n  |       ^^^^^\n"),
+        "{}",
+        rendered
+    );
+}
+
+#[test]
+fn lexical_errors_are_reported_instead_of_syntax_errors() {
+    let source = "var a = 1 @ 2;\nprint \"open;";
+    let errors = Glox::check(source).unwrap_err();
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(errors[0], GloxError::UnexpectedCharacter(_)));
+    assert!(matches!(errors[1], GloxError::UnterminatedString(_)));
+    assert_eq!(
+        errors[1].render(source, "test.lox"),
+        "\
+Lexical error: Unterminated string.
+  --> test.lox:2:7
+  |
+2 | print \"open;
+  |       ^
+  = help: add a closing '\"' to end the string
+"
+    );
+}
+
+#[test]
+fn the_binary_exits_with_65_for_errors_found_before_running() {
+    for source in [
+        "print 1;\nvar a = 1 @ 2;",
+        "print 1;\nprint ;",
+        "print 1;\nreturn;",
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_glox"))
+            .args(["-e", source])
+            .output()
+            .unwrap();
+        // Nothing runs
+        assert_eq!(output.stdout, b"", "{}", source);
+        assert_eq!(output.status.code(), Some(65), "{}", source);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(" --> <eval>:2:"), "{}", stderr);
+    }
+}
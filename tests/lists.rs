@@ -0,0 +1,153 @@
+// List literals, indexing and the list natives in src/stdlib.rs
+mod common;
+
+use common::{error, eval, eval_with};
+use glox::Glox;
+
+#[test]
+fn literals() {
+    assert_eq!(eval("[]"), "[]");
+    assert_eq!(
+        eval("[1, 2.5, \"a\", nil, true]"),
+        "[1, 2.5, \"a\", nil, true]"
+    );
+    assert_eq!(eval("[[1], [2, 3],]"), "[[1], [2, 3]]");
+    assert_eq!(eval_with("var x = 2;", "[x, x * x]"), "[2, 4]");
+}
+
+#[test]
+fn indexing() {
+    let setup = "var xs = [10, 20, 30];";
+    assert_eq!(eval_with(setup, "xs[0]"), "10");
+    assert_eq!(eval_with(setup, "xs[2]"), "30");
+    assert_eq!(eval_with(setup, "xs[-1]"), "30");
+    assert_eq!(eval_with(setup, "xs[-3]"), "10");
+    assert_eq!(eval_with(setup, "[[1, 2], [3]][0][1]"), "2");
+    assert_eq!(eval_with(setup, "xs[1] = 5"), "5");
+    assert_eq!(
+        eval_with("var xs = [10, 20, 30]; xs[-1] = nil;", "xs"),
+        "[10, 20, nil]"
+    );
+}
+
+#[test]
+fn indexing_errors() {
+    let out_of_bounds = "Index 3 is out of bounds for a list of length 3.";
+    assert_eq!(error("[1, 2, 3][3];"), out_of_bounds);
+    assert_eq!(error("var xs = [1, 2, 3]; xs[3] = 0;"), out_of_bounds);
+    assert_eq!(
+        error("[1, 2, 3][-4];"),
+        "Index -4 is out of bounds for a list of length 3."
+    );
+    assert_eq!(error("[1][0.0];"), "List indices must be ints, not float.");
+    assert_eq!(
+        error("\"abc\"[0];"),
+        "Only lists and maps can be indexed, not string."
+    );
+}
+
+#[test]
+fn lists_are_shared() {
+    let setup = "var a = [1]; var b = a; b[0] = 2;";
+    assert_eq!(eval_with(setup, "a"), "[2]");
+    assert_eq!(eval_with(setup, "a == b"), "true");
+    assert_eq!(eval_with(setup, "a == [2]"), "false");
+    assert_eq!(
+        eval_with("var a = [1]; var b = a; b[0] = 2; push(a, a);", "a"),
+        "[2, [...]]"
+    );
+}
+
+#[test]
+fn push_pop_insert_remove() {
+    let setup = "var xs = [1, 2];";
+    assert_eq!(
+        eval_with("var xs = [1, 2]; push(xs, 3);", "xs"),
+        "[1, 2, 3]"
+    );
+    assert_eq!(eval_with(setup, "pop(xs)"), "2");
+    assert_eq!(
+        eval_with("var xs = [1, 2]; insert(xs, 0, 0);", "xs"),
+        "[0, 1, 2]"
+    );
+    assert_eq!(
+        eval_with("var xs = [1, 2]; insert(xs, 2, 3);", "xs"),
+        "[1, 2, 3]"
+    );
+    assert_eq!(
+        eval_with("var xs = [1, 2]; insert(xs, -1, 9);", "xs"),
+        "[1, 9, 2]"
+    );
+    assert_eq!(eval_with(setup, "remove(xs, 0)"), "1");
+    assert_eq!(eval_with("var xs = [1, 2]; remove(xs, -1);", "xs"), "[1]");
+    assert_eq!(eval_with(setup, "len(xs)"), "2");
+
+    assert_eq!(error("pop([]);"), "Can't pop from an empty list.");
+    assert_eq!(
+        error("insert([1], 2, 0);"),
+        "Index 2 is out of bounds for a list of length 1."
+    );
+    assert_eq!(
+        error("remove([], 0);"),
+        "Index 0 is out of bounds for a list of length 0."
+    );
+    assert_eq!(
+        error("push(1, 2);"),
+        "Expected a list for argument 1 of 'push' but got int."
+    );
+}
+
+#[test]
+fn slice_copies_a_range() {
+    let setup = "var xs = [1, 2, 3, 4];";
+    assert_eq!(eval_with(setup, "slice(xs, 1, 3)"), "[2, 3]");
+    assert_eq!(eval_with(setup, "slice(xs, 0, -1)"), "[1, 2, 3]");
+    assert_eq!(eval_with(setup, "slice(xs, 4, 4)"), "[]");
+    assert_eq!(eval_with(setup, "slice(xs, 0, 4) == xs"), "false");
+    assert_eq!(
+        error("slice([1, 2], 2, 1);"),
+        "slice range 2..1 is out of bounds for a list of length 2."
+    );
+}
+
+#[test]
+fn contains_uses_equality() {
+    assert_eq!(eval("contains([1, \"a\"], \"a\")"), "true");
+    assert_eq!(eval("contains([1, 2], 2.0)"), "true");
+    assert_eq!(eval("contains([[1]], [1])"), "false");
+}
+
+#[test]
+fn reverse_and_sort_in_place() {
+    assert_eq!(
+        eval_with("var xs = [3, 1.5, -2, 10]; reverse(xs);", "xs"),
+        "[10, -2, 1.5, 3]"
+    );
+    assert_eq!(
+        eval_with("var xs = [3, 1.5, -2, 10]; sort(xs);", "xs"),
+        "[-2, 1.5, 3, 10]"
+    );
+    assert_eq!(
+        eval_with("var s = [\"b\", \"C\", \"a\"]; sort(s);", "s"),
+        "[\"C\", \"a\", \"b\"]"
+    );
+    assert_eq!(
+        error("sort([1, \"a\"]);"),
+        "Can only sort lists of just numbers or just strings."
+    );
+}
+
+#[test]
+fn split_makes_a_list() {
+    assert_eq!(
+        eval("split(\"a,b,,c\", \",\")"),
+        "[\"a\", \"b\", \"\", \"c\"]"
+    );
+    assert_eq!(eval("split(\"héy\", \"\")"), "[\"h\", \"é\", \"y\"]");
+}
+
+#[test]
+fn formatting_round_trips() {
+    let source = "var a = [1, [2, 3]];\na[1][0] = a[-1][0];\n";
+    assert_eq!(Glox::format(source).unwrap(), source);
+}
@@ -0,0 +1,154 @@
+// Map literals, indexing and the map natives in src/stdlib.rs
+mod common;
+
+use common::{error, eval, eval_with};
+use glox::Glox;
+
+#[test]
+fn literals() {
+    assert_eq!(eval("{}"), "{}");
+    assert_eq!(
+        eval("{\"a\": 1, 2: \"b\", nil: [true],}"),
+        "{\"a\": 1, 2: \"b\", nil: [true]}"
+    );
+    assert_eq!(eval_with("var k = \"x\";", "{k: k}"), "{\"x\": \"x\"}");
+    assert_eq!(eval("{\"a\": 1, \"a\": 2}"), "{\"a\": 2}");
+}
+
+#[test]
+fn braces_starting_a_statement_are_blocks() {
+    let errors = Glox::new().run("{\"a\": 1};").unwrap_err();
+    let diagnostic = errors[0].diagnostic();
+    assert_eq!(diagnostic.message, "Expect ';' after expression.");
+    assert_eq!(
+        diagnostic.help.as_deref(),
+        Some("a '{' that starts a statement begins a block, put a map literal in parentheses")
+    );
+    assert_eq!(eval_with("({\"a\": 1})[\"a\"];", "1"), "1");
+    assert_eq!(eval_with("{ var a = 1; }", "{\"a\": 1}[\"a\"]"), "1");
+}
+
+#[test]
+fn indexing() {
+    let setup = "var m = {\"a\": 1};";
+    assert_eq!(eval_with(setup, "m[\"a\"]"), "1");
+    assert_eq!(eval_with(setup, "m[\"b\"] = 2"), "2");
+    assert_eq!(eval_with("var m = {}; m[\"b\"] = 2;", "m"), "{\"b\": 2}");
+    assert_eq!(
+        eval_with("var m = {\"a\": {}}; m[\"a\"][1] = 2;", "m"),
+        "{\"a\": {1: 2}}"
+    );
+    assert_eq!(
+        error("var m = {}; m[\"a\"];"),
+        "Key \"a\" isn't in the map."
+    );
+}
+
+#[test]
+fn keys_compare_like_equality() {
+    let setup = "var m = {1: \"int\", \"1\": \"string\", true: 1, nil: 0};";
+    assert_eq!(eval_with(setup, "m[1.0]"), "int");
+    assert_eq!(eval_with(setup, "m[\"1\"]"), "string");
+    assert_eq!(eval_with(setup, "m[true]"), "1");
+    assert_eq!(eval_with(setup, "m[nil]"), "0");
+    assert_eq!(
+        eval_with("var m = {1: \"a\"}; m[1.0] = \"b\";", "m"),
+        "{1: \"b\"}"
+    );
+    assert_eq!(eval_with("var m = {-0.0: 1};", "m[0]"), "1");
+    assert_eq!(eval_with("var m = {0.5: 1};", "m[1 / 2]"), "1");
+}
+
+#[test]
+fn large_keys_agree_with_equality() {
+    // 2^53 + 1 rounds to 2^53 as a float, but the int and float aren't ==
+    let setup = "var m = {}; m[9007199254740993] = \"int\"; m[9007199254740992.0] = \"float\";";
+    assert_eq!(eval("9007199254740993 == 9007199254740992.0"), "false");
+    assert_eq!(eval_with(setup, "len(m)"), "2");
+    assert_eq!(eval_with(setup, "m[9007199254740993]"), "int");
+    assert_eq!(eval_with(setup, "m[9007199254740992]"), "float");
+    // Past 2^53, whole floats are still the same key as the int they equal
+    let setup = "var m = {1152921504606846976: \"a\"};";
+    assert_eq!(eval("1152921504606846976 == 1152921504606846976.0"), "true");
+    assert_eq!(eval_with(setup, "m[1152921504606846976.0]"), "a");
+    assert_eq!(eval_with(setup, "has(m, 1152921504606846977)"), "false");
+    // Floats too big for an int are keys of their own
+    let setup = "var m = {1e300: 1, 9223372036854775807: 2};";
+    assert_eq!(eval_with(setup, "m[1e300]"), "1");
+    assert_eq!(eval_with(setup, "has(m, 9223372036854775808.0)"), "false");
+    assert_eq!(
+        eval("9223372036854775807 == 9223372036854775808.0"),
+        "false"
+    );
+}
+
+#[test]
+fn unhashable_keys() {
+    let message = "Only strings, numbers, bools and nil can be map keys, not list.";
+    assert_eq!(error("var m = {[1]: 2};"), message);
+    assert_eq!(error("var m = {}; m[[1]] = 2;"), message);
+    assert_eq!(error("has({}, [1]);"), message);
+    assert_eq!(
+        error("var m = {}; m[0 / 0] = 1;"),
+        "nan can't be a map key, it isn't equal to itself."
+    );
+}
+
+#[test]
+fn entries_keep_insertion_order() {
+    let setup = "var m = {\"b\": 1, \"a\": 2, \"c\": 3}; m[\"a\"] = 20;";
+    assert_eq!(eval_with(setup, "keys(m)"), "[\"b\", \"a\", \"c\"]");
+    assert_eq!(eval_with(setup, "values(m)"), "[1, 20, 3]");
+    assert_eq!(
+        eval_with(&format!("{} remove(m, \"b\"); m[\"b\"] = 0;", setup), "m"),
+        "{\"a\": 20, \"c\": 3, \"b\": 0}"
+    );
+}
+
+#[test]
+fn has_remove_and_len() {
+    let setup = "var m = {\"a\": 1, \"b\": 2};";
+    assert_eq!(eval_with(setup, "has(m, \"a\")"), "true");
+    assert_eq!(eval_with(setup, "has(m, \"z\")"), "false");
+    assert_eq!(eval_with(setup, "remove(m, \"a\")"), "1");
+    assert_eq!(
+        eval_with(&format!("{} remove(m, \"a\");", setup), "m"),
+        "{\"b\": 2}"
+    );
+    assert_eq!(eval_with(setup, "len(m)"), "2");
+    assert_eq!(error("remove({}, \"a\");"), "Key \"a\" isn't in the map.");
+    assert_eq!(
+        error("keys([]);"),
+        "Expected a map for argument 1 of 'keys' but got list."
+    );
+}
+
+#[test]
+fn maps_are_shared() {
+    let setup = "var a = {}; var b = a; b[1] = 2;";
+    assert_eq!(eval_with(setup, "a"), "{1: 2}");
+    assert_eq!(eval_with(setup, "a == b"), "true");
+    assert_eq!(eval_with(setup, "a == {1: 2}"), "false");
+    assert_eq!(
+        eval_with(&format!("{} a[0] = a;", setup), "a"),
+        "{1: 2, 0: {...}}"
+    );
+}
+
+#[test]
+fn formatting_round_trips() {
+    let source = "var m = {\"a\": [1], 2: {}};\nprint ({\"x\": m})[\"x\"];\n";
+    assert_eq!(Glox::format(source).unwrap(), source);
+}
+
+#[test]
+fn entries_can_be_assigned_to_but_calls_cannot() {
+    assert_eq!(eval_with("var m = {}; m[\"a\"] = 1;", "m"), "{\"a\": 1}");
+    let errors = Glox::check("var m = {}; keys(m) = 1;").unwrap_err();
+    let diagnostic = errors[0].diagnostic();
+    assert_eq!(diagnostic.message, "Invalid assignment target.");
+    assert_eq!(
+        diagnostic.help.as_deref(),
+        Some("only variables, properties and index expressions can be assigned to")
+    );
+}
@@ -0,0 +1,218 @@
+// Number literals, see Scanner::handle_number, and how numbers compare and print
+mod common;
+
+use glox::{Glox, GloxError};
+
+// Evaluates an expression and returns its type and value, as in "int 255"
+fn eval(source: &str) -> String {
+    let value = common::value(source);
+    format!("{} {}", value.type_name(), value)
+}
+
+// The message and help of the error the literal is rejected with
+fn error(source: &str) -> (String, Option<String>) {
+    match Glox::tokenize(source) {
+        Ok(_) => panic!("{} should have failed to scan", source),
+        Err(errors) => match &errors[0] {
+            GloxError::InvalidNumber(diagnostic) => {
+                (diagnostic.message.clone(), diagnostic.help.clone())
+            }
+            e => panic!("{} failed with the wrong kind of error: {}", source, e),
+        },
+    }
+}
+
+fn message(source: &str) -> String {
+    error(source).0
+}
+
+#[test]
+fn decimal() {
+    assert_eq!(eval("0"), "int 0");
+    assert_eq!(eval("123"), "int 123");
+    assert_eq!(eval("007"), "int 7");
+    assert_eq!(eval("123.45"), "float 123.45");
+    assert_eq!(eval("9223372036854775807"), "int 9223372036854775807");
+}
+
+#[test]
+fn hexadecimal() {
+    assert_eq!(eval("0xFF"), "int 255");
+    assert_eq!(eval("0xff"), "int 255");
+    assert_eq!(eval("0XaB"), "int 171");
+    assert_eq!(eval("0x7fff_ffff_ffff_ffff"), "int 9223372036854775807");
+    assert_eq!(
+        message("0xFG"),
+        "Invalid digit 'G' for a hexadecimal number."
+    );
+    assert_eq!(message("0x"), "Expected digits after '0x'.");
+}
+
+#[test]
+fn binary() {
+    assert_eq!(eval("0b1010"), "int 10");
+    assert_eq!(eval("0B1111_0000"), "int 240");
+    assert_eq!(message("0b102"), "Invalid digit '2' for a binary number.");
+    assert_eq!(message("0b"), "Expected digits after '0b'.");
+}
+
+#[test]
+fn octal() {
+    assert_eq!(eval("0o17"), "int 15");
+    assert_eq!(eval("0O777"), "int 511");
+    assert_eq!(message("0o8"), "Invalid digit '8' for an octal number.");
+    assert_eq!(message("0o"), "Expected digits after '0o'.");
+}
+
+#[test]
+fn underscore_separators() {
+    assert_eq!(eval("1_000_000"), "int 1000000");
+    assert_eq!(eval("1_0.2_5"), "float 10.25");
+    assert_eq!(eval("1e1_0"), "float 10000000000");
+    let misplaced = "Underscores in a number must go between digits.";
+    assert_eq!(message("1__000"), misplaced);
+    assert_eq!(message("1000_"), misplaced);
+    assert_eq!(message("0x_FF"), misplaced);
+    assert_eq!(message("1_.5"), misplaced);
+}
+
+#[test]
+fn exponents() {
+    assert_eq!(eval("1e3"), "float 1000");
+    assert_eq!(eval("1E3"), "float 1000");
+    assert_eq!(eval("2.5e+2"), "float 250");
+    assert_eq!(eval("1e-9"), "float 1e-9");
+    assert_eq!(eval("0e0"), "float 0");
+    assert_eq!(message("1e"), "Expected digits after '1e'.");
+    assert_eq!(message("1e+"), "Expected digits after '1e+'.");
+    assert_eq!(message("1e5x"), "Invalid digit 'x' for a decimal number.");
+}
+
+#[test]
+fn leading_dot_is_rejected() {
+    let (message, help) = error(".5");
+    assert_eq!(message, "Numbers can't start with '.'.");
+    assert_eq!(
+        help.as_deref(),
+        Some("add a zero before the '.', as in 0.5")
+    );
+    assert_eq!(error("1 + .25").0, "Numbers can't start with '.'.");
+}
+
+#[test]
+fn trailing_dot_is_a_property_access() {
+    let tokens = Glox::tokenize("1.abs").unwrap();
+    let lexemes: Vec<&str> = tokens.iter().map(|t| t.lexeme).collect();
+    assert_eq!(lexemes, ["1", ".", "abs", ""]);
+}
+
+#[test]
+fn letters_after_digits_are_rejected() {
+    assert_eq!(message("12px"), "Invalid digit 'p' for a decimal number.");
+}
+
+#[test]
+fn literals_that_overflow() {
+    let (message, help) = error("9223372036854775808");
+    assert_eq!(message, "Integer literal is too large.");
+    assert_eq!(
+        help.as_deref(),
+        Some("the largest int is 9223372036854775807, add '.0' to make it a float")
+    );
+    assert_eq!(
+        error("0x8000_0000_0000_0000").0,
+        "Integer literal is too large."
+    );
+    assert_eq!(
+        error(&format!("0b1{}", "0".repeat(63))).0,
+        "Integer literal is too large."
+    );
+    assert_eq!(
+        error("0o1000000000000000000000").0,
+        "Integer literal is too large."
+    );
+    assert_eq!(error("1e309").0, "Float literal is too large.");
+
+    // Big decimals are fine as floats, and the smallest int is reachable by negating
+    assert_eq!(eval("9223372036854775808.0"), "float 9223372036854776000");
+    assert_eq!(eval("-9223372036854775807 - 1"), "int -9223372036854775808");
+}
+
+#[test]
+fn errors_point_at_the_whole_literal() {
+    let errors = Glox::tokenize("print 0b102;").unwrap_err();
+    let span = errors[0].diagnostic().span;
+    assert_eq!((span.start, span.end), (6, 11));
+}
+
+// 2^53 + 1 is the first int a float can't hold, it rounds to 2^53 when converted
+#[test]
+fn ints_and_floats_compare_exactly() {
+    let cases = [
+        ("9007199254740993 > 9007199254740992.0", "true"),
+        ("9007199254740993 >= 9007199254740992.0", "true"),
+        ("9007199254740993 <= 9007199254740992.0", "false"),
+        ("9007199254740993 == 9007199254740992.0", "false"),
+        ("9007199254740992.0 < 9007199254740993", "true"),
+        ("9007199254740992 <= 9007199254740992.0", "true"),
+        ("9007199254740992 < 9007199254740992.0", "false"),
+        // i64::MAX as a float rounds up to 2^63
+        ("9223372036854775807 < 9223372036854775808.0", "true"),
+        ("-9223372036854775807 - 1 >= -9223372036854775808.0", "true"),
+        ("-9223372036854775807 - 1 > -9223372036854775808.0", "false"),
+        ("-9223372036854775807 - 1 > -9223372036854777856.0", "true"),
+        // Fractions between two ints
+        ("1 < 1.5", "true"),
+        ("2 > 1.5", "true"),
+        ("-1 < -0.5", "true"),
+        ("-2 < -1.5", "true"),
+        ("-1 > -1.5", "true"),
+        ("0 == -0.0", "true"),
+        ("0 >= -0.0", "true"),
+        ("1 < 1e300", "true"),
+        ("1 > -1 / 0", "true"),
+        ("1 < 1 / 0", "true"),
+        ("1 < 0 / 0", "false"),
+        ("1 >= 0 / 0", "false"),
+        ("0 / 0 <= 1", "false"),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(common::eval_both("", source), expected, "{}", source);
+    }
+}
+
+// See format_number
+#[test]
+fn how_floats_print() {
+    let cases = [
+        ("0 / 0", "float nan"),
+        ("-(0 / 0)", "float nan"),
+        ("1 / 0", "float inf"),
+        ("-1 / 0", "float -inf"),
+        ("-0.0", "float -0"),
+        ("0.0 * -1", "float -0"),
+        ("-0", "int 0"),
+        // Whole floats print without a trailing .0
+        ("1.0", "float 1"),
+        ("4 / 2", "float 2"),
+        ("2.5e3", "float 2500"),
+        ("0.1 + 0.2", "float 0.30000000000000004"),
+        ("1 / 3", "float 0.3333333333333333"),
+        // Exponent form outside of 1e-7 to 1e21
+        ("1e20", "float 100000000000000000000"),
+        ("1e21", "float 1e+21"),
+        ("-1e21", "float -1e+21"),
+        ("1.5e300", "float 1.5e+300"),
+        ("1e-7", "float 0.0000001"),
+        ("1e-8", "float 1e-8"),
+        ("-1.5e-9", "float -1.5e-9"),
+        ("5e-324", "float 5e-324"),
+        ("1.7976931348623157e308", "float 1.7976931348623157e+308"),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(eval(source), expected, "{}", source);
+    }
+    // Strings and lists show them the same way
+    assert_eq!(common::eval("\"${1.0} ${1e21}\""), "1 1e+21");
+    assert_eq!(common::eval("[1.0, -0.0, 0 / 0]"), "[1, -0, nan]");
+}
@@ -0,0 +1,97 @@
+// How binary operators group, compare and do arithmetic, see Parser::factor
+// and operators::binary
+mod common;
+
+use common::{eval, eval_both, eval_error_both};
+
+// -2^63, which can't be written as a literal as 2^63 is out of range
+const MIN: &str = "(-9223372036854775807 - 1)";
+
+#[test]
+fn comparisons_include_equal_operands_only_when_they_should() {
+    assert_eq!(eval("1 <= 1"), "true");
+    assert_eq!(eval("1 <= 2"), "true");
+    assert_eq!(eval("2 <= 1"), "false");
+    assert_eq!(eval("1 >= 1"), "true");
+    assert_eq!(eval("1 < 1"), "false");
+    assert_eq!(eval("1 > 1"), "false");
+}
+
+#[test]
+fn factors_group_to_the_left() {
+    assert_eq!(eval("8 / 4 / 2 == 1"), "true");
+    assert_eq!(eval("2 * 6 / 3 == 4"), "true");
+    assert_eq!(eval("12 / 2 * 3 == 18"), "true");
+    assert_eq!(eval("8 - 4 - 2 == 2"), "true");
+}
+
+#[test]
+fn integer_division_rounds_towards_negative_infinity() {
+    assert_eq!(eval_both("", "7 ~/ 2"), "3");
+    assert_eq!(eval_both("", "-7 ~/ 2"), "-4");
+    assert_eq!(eval_both("", "7 ~/ -2"), "-4");
+    assert_eq!(eval_both("", "-7 ~/ -2"), "3");
+    assert_eq!(eval_both("", "6 ~/ 3"), "2");
+    // Floats give a whole float
+    assert_eq!(eval_both("", "7.5 ~/ 2"), "3");
+    assert_eq!(eval_both("", "type(7.5 ~/ 2)"), "float");
+    assert_eq!(eval_both("", "-7.5 ~/ 2"), "-4");
+    // It groups like the other factors
+    assert_eq!(eval_both("", "100 ~/ 10 ~/ 3"), "3");
+    assert_eq!(eval_both("", "2 * 7 ~/ 4"), "3");
+}
+
+#[test]
+fn remainders_have_the_sign_of_the_divisor() {
+    assert_eq!(eval_both("", "7 % 3"), "1");
+    assert_eq!(eval_both("", "-7 % 3"), "2");
+    assert_eq!(eval_both("", "7 % -3"), "-2");
+    assert_eq!(eval_both("", "-7 % -3"), "-1");
+    assert_eq!(eval_both("", "-6 % 3"), "0");
+    assert_eq!(eval_both("", "-7.5 % 2"), "0.5");
+    assert_eq!(eval_both("", "7.5 % -2"), "-0.5");
+    // (a ~/ b) * b + a % b == a
+    for (a, b) in [(7, 3), (-7, 3), (7, -3), (-7, -3)] {
+        let source = format!("({a} ~/ {b}) * {b} + {a} % {b} == {a}");
+        assert_eq!(eval_both("", &source), "true", "{}", source);
+    }
+}
+
+#[test]
+fn dividing_by_zero() {
+    assert_eq!(eval_error_both("1 ~/ 0"), "Division by zero.");
+    assert_eq!(eval_error_both("1 % 0"), "Division by zero.");
+    assert_eq!(eval_error_both("-1 % 0"), "Division by zero.");
+    // / always gives a float, so it follows IEEE 754 like floats do
+    assert_eq!(eval_both("", "1 / 0"), "inf");
+    assert_eq!(eval_both("", "-1 / 0"), "-inf");
+    assert_eq!(eval_both("", "0 / 0 == 0 / 0"), "false");
+    assert_eq!(eval_both("", "1.0 ~/ 0"), "inf");
+    assert_eq!(eval_both("", "1.5 % 0 == 1.5 % 0"), "false");
+}
+
+#[test]
+fn integer_overflow_is_an_error() {
+    assert_eq!(
+        eval_error_both("9223372036854775807 + 1"),
+        "Integer overflow."
+    );
+    assert_eq!(eval_error_both(&format!("{MIN} - 1")), "Integer overflow.");
+    assert_eq!(
+        eval_error_both("9223372036854775807 * 2"),
+        "Integer overflow."
+    );
+    assert_eq!(eval_error_both(&format!("-{MIN}")), "Integer overflow.");
+    assert_eq!(
+        eval_error_both(&format!("{MIN} ~/ -1")),
+        "Integer overflow."
+    );
+    // The remainder is 0 even though the quotient overflows
+    assert_eq!(eval_both("", &format!("{MIN} % -1")), "0");
+    assert_eq!(eval_both("", &format!("{MIN} ~/ 1 == {MIN}")), "true");
+    // Floats don't overflow, they lose precision
+    assert_eq!(
+        eval_both("", "9223372036854775807 + 1.0"),
+        "9223372036854776000"
+    );
+}
@@ -0,0 +1,98 @@
+// Where the scanner says tokens are in source that isn't plain ASCII, see Scanner::new
+use glox::{Glox, Span, Token, TokenType};
+
+fn tokens(source: &str) -> Vec<Token<'_>> {
+    Glox::tokenize(source).unwrap()
+}
+
+// The type, text, line and column of each token
+fn positions(source: &str) -> Vec<(TokenType, String, usize, usize)> {
+    tokens(source)
+        .into_iter()
+        .map(|t| {
+            (
+                t.token_type,
+                t.lexeme.to_string(),
+                t.span.line,
+                t.span.column,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn unicode_identifiers() {
+    let tokens = tokens("var π = 3.14; naïve_名前 = π;");
+    assert_eq!(tokens[1].token_type, TokenType::Identifier);
+    assert_eq!(tokens[1].lexeme, "π");
+    assert_eq!(tokens[5].lexeme, "naïve_名前");
+    assert_eq!(tokens[5].token_type, TokenType::Identifier);
+    assert_eq!(
+        Glox::new().eval("π").unwrap_err()[0].to_string(),
+        "RuntimeError: Undefined variable 'π'. at line: 1:1"
+    );
+    let mut glox = Glox::new();
+    glox.run("var π = 3; var 変数 = π * 2;").unwrap();
+    assert_eq!(glox.eval("変数").unwrap().to_string(), "6");
+    // Symbols that aren't letters still can't start one
+    assert!(Glox::tokenize("var € = 1;").is_err());
+}
+
+#[test]
+fn a_byte_order_mark_is_skipped() {
+    let source = "\u{feff}print 1;";
+    assert_eq!(
+        positions(source)[..2],
+        [
+            (TokenType::Print, "print".to_string(), 1, 1),
+            (TokenType::Number, "1".to_string(), 1, 7),
+        ]
+    );
+    // Offsets still count it, so spans slice the source as given
+    let print = &tokens(source)[0];
+    assert_eq!(print.span.start, 3);
+    assert_eq!(&source[print.span.start..print.span.end], "print");
+    assert!(Glox::new().run(source).is_ok());
+    // Anywhere else it's an unexpected character
+    assert!(Glox::tokenize("print\u{feff} 1;").is_err());
+}
+
+#[test]
+fn crlf_line_endings() {
+    let source = "var a = 1;\r\nvar b =\r\n  \"x\r\ny\";\r\nprint b;";
+    let positions = positions(source);
+    assert_eq!(positions[5], (TokenType::Var, "var".to_string(), 2, 1));
+    assert_eq!(positions[6], (TokenType::Identifier, "b".to_string(), 2, 5));
+    assert_eq!(positions[8].0, TokenType::String);
+    assert_eq!((positions[8].2, positions[8].3), (3, 3));
+    assert_eq!(positions[10], (TokenType::Print, "print".to_string(), 5, 1));
+    // The line break inside the string is read as \n
+    let mut glox = Glox::new();
+    glox.run(source).unwrap();
+    assert_eq!(glox.eval("b").unwrap().to_string(), "x\ny");
+}
+
+#[test]
+fn spans_after_multibyte_characters() {
+    // "é" is 2 bytes and "😀" is 4, but each is one column
+    let source = "print \"é😀\" + x;";
+    let tokens = tokens(source);
+    let plus = &tokens[2];
+    assert_eq!(plus.token_type, TokenType::Plus);
+    assert_eq!(
+        plus.span,
+        Span {
+            start: 15,
+            end: 16,
+            line: 1,
+            column: 12
+        }
+    );
+    let x = &tokens[3];
+    assert_eq!(&source[x.span.start..x.span.end], "x");
+    assert_eq!(x.span.column, 14);
+    // Errors point at the right column too
+    let errors = Glox::new().run(source).unwrap_err();
+    let span = errors[0].diagnostic().span;
+    assert_eq!((span.line, span.column), (1, 14));
+}
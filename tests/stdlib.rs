@@ -0,0 +1,156 @@
+// The built-in functions every program starts with, see src/stdlib.rs
+mod common;
+
+use common::{eval, eval_error};
+use glox::Glox;
+
+#[test]
+fn clock_counts_up_in_seconds() {
+    let mut glox = Glox::new();
+    glox.run("var a = clock(); var b = clock();").unwrap();
+    assert_eq!(glox.eval("type(a)").unwrap().to_string(), "float");
+    assert_eq!(glox.eval("b >= a").unwrap().to_string(), "true");
+}
+
+#[test]
+fn input_is_a_function() {
+    assert_eq!(eval("type(input)"), "function");
+}
+
+#[test]
+fn len_counts_characters() {
+    assert_eq!(eval("len(\"\")"), "0");
+    assert_eq!(eval("len(\"lox\")"), "3");
+    assert_eq!(eval("len(\"héllo\")"), "5");
+    assert_eq!(
+        eval_error("len(1)"),
+        "Expected a string, list or map for argument 1 of 'len' but got int."
+    );
+}
+
+#[test]
+fn str_matches_print() {
+    assert_eq!(eval("str(1.5) + \"!\""), "1.5!");
+    assert_eq!(eval("str(nil)"), "nil");
+    assert_eq!(eval("str(true)"), "true");
+    assert_eq!(eval("str(clock)"), "<native fn clock>");
+}
+
+#[test]
+fn num_parses_strings() {
+    assert_eq!(eval("num(\"42\") + 1"), "43");
+    assert_eq!(eval("num(\" 2.5 \")"), "2.5");
+    assert_eq!(eval("num(7)"), "7");
+    assert_eq!(eval("num(\"seven\")"), "nil");
+    assert_eq!(eval_error("num(true)"), "Can't convert bool to a number.");
+}
+
+#[test]
+fn type_names_every_kind_of_value() {
+    assert_eq!(eval("type(1)"), "int");
+    assert_eq!(eval("type(1.5)"), "float");
+    assert_eq!(eval("type(\"s\")"), "string");
+    assert_eq!(eval("type(false)"), "bool");
+    assert_eq!(eval("type(nil)"), "nil");
+    assert_eq!(eval("type(sqrt)"), "function");
+    assert_eq!(eval("type([])"), "list");
+    assert_eq!(eval("type({})"), "map");
+
+    let mut glox = Glox::new();
+    glox.run("class A {} fun f() {} var a = A();").unwrap();
+    assert_eq!(glox.eval("type(f)").unwrap().to_string(), "function");
+    assert_eq!(glox.eval("type(A)").unwrap().to_string(), "class");
+    assert_eq!(glox.eval("type(a)").unwrap().to_string(), "instance");
+}
+
+#[test]
+fn math() {
+    assert_eq!(eval("sqrt(16)"), "4");
+    assert_eq!(eval("floor(2.7)"), "2");
+    assert_eq!(eval("floor(-2.5)"), "-3");
+    assert_eq!(eval("abs(-3)"), "3");
+    assert_eq!(eval("pow(2, 10)"), "1024");
+    assert_eq!(eval("min(3, -1)"), "-1");
+    assert_eq!(eval("max(3, -1)"), "3");
+    assert_eq!(eval("type(abs(-3))"), "int");
+    assert_eq!(eval("type(max(1, 2.5))"), "float");
+    assert_eq!(
+        eval_error("sqrt(\"4\")"),
+        "Expected a number for argument 1 of 'sqrt' but got string."
+    );
+    assert_eq!(eval_error("max(1)"), "Expected 2 arguments but got 1.");
+}
+
+#[test]
+fn random_is_in_range() {
+    let mut glox = Glox::new();
+    glox.run(
+        "var ok = true;
+         for (var i = 0; i < 1000; i = i + 1) {
+           var r = random();
+           if (r < 0 or r >= 1) ok = false;
+         }",
+    )
+    .unwrap();
+    assert_eq!(glox.get_global("ok").unwrap().to_string(), "true");
+}
+
+#[test]
+fn seeding_makes_random_repeatable() {
+    let sequence = "seed(42); var a = random(); var b = random();";
+    let mut first = Glox::new();
+    first.run(sequence).unwrap();
+    let mut second = Glox::new();
+    second.run(sequence).unwrap();
+    for name in ["a", "b"] {
+        assert_eq!(
+            first.get_global(name).unwrap().to_string(),
+            second.get_global(name).unwrap().to_string()
+        );
+    }
+    assert_eq!(first.eval("a == b").unwrap().to_string(), "false");
+
+    let mut other = Glox::new();
+    other.run("seed(7); var a = random();").unwrap();
+    assert_ne!(
+        first.eval("a").unwrap().to_string(),
+        other.eval("a").unwrap().to_string()
+    );
+}
+
+#[test]
+fn substr_takes_start_and_length() {
+    assert_eq!(eval("substr(\"hello\", 1, 3)"), "ell");
+    assert_eq!(eval("substr(\"hello\", 5, 0)"), "");
+    assert_eq!(eval("substr(\"héllo\", 1, 1)"), "é");
+    assert_eq!(
+        eval_error("substr(\"hello\", 3, 5)"),
+        "substr range 3..8 is out of bounds for a string of length 5."
+    );
+    assert_eq!(
+        eval_error("substr(\"hello\", -1, 2)"),
+        "substr start must be a whole number that isn't negative, got -1."
+    );
+}
+
+#[test]
+fn index_of_finds_the_first_match() {
+    assert_eq!(eval("index_of(\"banana\", \"an\")"), "1");
+    assert_eq!(eval("index_of(\"héllo\", \"l\")"), "2");
+    assert_eq!(eval("index_of(\"banana\", \"x\")"), "-1");
+    assert_eq!(eval("index_of(\"banana\", \"\")"), "0");
+}
+
+#[test]
+fn case_and_whitespace() {
+    assert_eq!(eval("upper(\"Lox\")"), "LOX");
+    assert_eq!(eval("lower(\"Lox\")"), "lox");
+    assert_eq!(eval("trim(\"  lox \")"), "lox");
+}
+
+#[test]
+fn natives_can_be_shadowed() {
+    let mut glox = Glox::new();
+    glox.run("fun len(x) { return 99; }").unwrap();
+    assert_eq!(glox.eval("len(\"abc\")").unwrap().to_string(), "99");
+}
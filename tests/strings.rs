@@ -0,0 +1,115 @@
+// String literals: escapes, raw and triple-quoted strings, and interpolation
+mod common;
+
+use common::{eval, eval_both};
+use glox::{Glox, GloxError};
+
+// The scanner errors the source fails with, as (message, text the span covers)
+fn scan_errors(source: &str) -> Vec<(String, String)> {
+    let errors = Glox::check(source).unwrap_err();
+    errors
+        .iter()
+        .map(|e| {
+            assert!(matches!(e, GloxError::InvalidEscape(_)), "{}", e);
+            let diagnostic = e.diagnostic();
+            let span = diagnostic.span;
+            (
+                diagnostic.message.clone(),
+                source[span.start..span.end].to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn escapes() {
+    assert_eq!(eval(r#""a\tb\nc""#), "a\tb\nc");
+    assert_eq!(eval(r#""\r\0""#), "\r\0");
+    assert_eq!(eval(r#""\\ \" \$""#), "\\ \" $");
+    assert_eq!(eval(r#""\${x}""#), "${x}");
+    assert_eq!(eval(r#""\u{1F600}\u{e9}""#), "😀é");
+    assert_eq!(eval(r#"len("\u{1F600}")"#), "1");
+}
+
+#[test]
+fn invalid_escapes() {
+    assert_eq!(
+        scan_errors(r#"print "a\qb";"#),
+        [(
+            "Invalid escape sequence '\\q'.".to_string(),
+            "\\q".to_string()
+        )]
+    );
+    assert_eq!(
+        scan_errors(r#"print "\u{110000}";"#)[0].0,
+        "Invalid unicode escape."
+    );
+    assert_eq!(
+        scan_errors(r#"print "\u1F600";"#)[0].0,
+        "Invalid unicode escape."
+    );
+    // Every bad escape is reported, not just the first
+    assert_eq!(scan_errors(r#"print "\a\b";"#).len(), 2);
+}
+
+#[test]
+fn raw_strings() {
+    assert_eq!(eval(r#"r"C:\new\${dir}""#), r"C:\new\${dir}");
+    assert_eq!(eval(r#"r"${1 + 1}""#), "${1 + 1}");
+    assert_eq!(eval(r#"len(r"\n")"#), "2");
+}
+
+#[test]
+fn triple_quoted_strings() {
+    assert_eq!(eval(r#""""say "hi" twice""""#), r#"say "hi" twice"#);
+    assert_eq!(eval("\"\"\"one\ntwo\"\"\""), "one\ntwo");
+    // Line breaks are \n whatever the file uses
+    assert_eq!(eval("\"\"\"one\r\ntwo\"\"\""), "one\ntwo");
+    assert_eq!(eval(r#"r"""\d+ "x" \n""""#), r#"\d+ "x" \n"#);
+    assert_eq!(eval(r#""""${"a" + "b"} "c" """"#), r#"ab "c" "#);
+}
+
+#[test]
+fn interpolation() {
+    assert_eq!(
+        eval_both("var n = 2;", r#""${n} + ${n} = ${n + n}""#),
+        "2 + 2 = 4"
+    );
+    assert_eq!(eval_both("", r#""${nil}${true}${1.5}""#), "niltrue1.5");
+    assert_eq!(eval_both("", r#""${[1, "a"]}""#), r#"[1, "a"]"#);
+    assert_eq!(eval_both("", r#""${{"k": 1}["k"]}""#), "1");
+    assert_eq!(eval_both("fun f() {}", r#""${f}""#), "<fn f>");
+}
+
+#[test]
+fn nested_interpolation() {
+    assert_eq!(eval_both("var x = 1;", r#""a${"b${x + 1}c"}d""#), "ab2cd");
+    assert_eq!(eval_both("", r#""${"${"${3}"}"}""#), "3");
+}
+
+#[test]
+fn interpolation_ignores_a_shadowed_str() {
+    assert_eq!(eval_both("var str = 3;", r#""a${1}""#), "a1");
+    assert_eq!(
+        eval_both("fun f(str) { return \"v=${1}\" + str; }", r#"f("!")"#),
+        "v=1!"
+    );
+    assert_eq!(
+        eval_both("fun str(x) { return \"nope\"; }", r#""${2}""#),
+        "2"
+    );
+}
+
+#[test]
+fn interpolation_errors() {
+    let errors = Glox::check(r#"print "a${}b";"#).unwrap_err();
+    assert_eq!(
+        errors[0].diagnostic().message,
+        "Expect expression inside '${}'."
+    );
+    let errors = Glox::check(r#"print "a${1 2}b";"#).unwrap_err();
+    assert_eq!(
+        errors[0].diagnostic().message,
+        "Expect '}' after interpolated expression."
+    );
+}